@@ -0,0 +1,217 @@
+use std::ptr::NonNull;
+
+use super::{iter, BinTree, Monoid, Node};
+
+type NodePtr<K, V, A> = NonNull<Node<K, V, A>>;
+
+/// Neighbour of `node` in the order of entries, where `None` stands for the
+/// "ghost" position after the last and before the first entry
+fn step<K, V, A>(
+    tree: &BinTree<K, V, A>,
+    node: Option<NodePtr<K, V, A>>,
+    forward: bool,
+) -> Option<NodePtr<K, V, A>> {
+    match (node, forward) {
+        (Some(node), true) => unsafe { iter::next(node) },
+        (Some(node), false) => unsafe { iter::prev(node) },
+        (None, true) => {
+            tree.root_ptr().map(|root| unsafe { iter::first(root) })
+        }
+        (None, false) => {
+            tree.root_ptr().map(|root| unsafe { iter::last(root) })
+        }
+    }
+}
+
+/// Read-only cursor, that can move over the entries in both directions
+///
+/// Moving past either end goes to the "ghost" position, which is followed by
+/// the first entry and preceded by the last one.
+pub struct Cursor<'t, K, V, A = ()> {
+    tree: &'t BinTree<K, V, A>,
+    node: Option<NodePtr<K, V, A>>,
+}
+
+impl<'t, K, V, A> Clone for Cursor<'t, K, V, A> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'t, K, V, A> Copy for Cursor<'t, K, V, A> {}
+
+// SAFETY: the cursor is a shared borrow of the tree
+unsafe impl<'t, K: Sync, V: Sync, A: Sync> Send for Cursor<'t, K, V, A> {}
+unsafe impl<'t, K: Sync, V: Sync, A: Sync> Sync for Cursor<'t, K, V, A> {}
+
+impl<'t, K, V, A> Cursor<'t, K, V, A> {
+    pub(super) fn new(
+        tree: &'t BinTree<K, V, A>,
+        node: Option<NodePtr<K, V, A>>,
+    ) -> Self {
+        Self { tree, node }
+    }
+
+    /// Entry under the cursor, `None` at the ghost position
+    pub fn current(&self) -> Option<(&'t K, &'t V)> {
+        self.node.map(|node| unsafe { node.as_ref() }.pair_ref())
+    }
+
+    pub fn move_next(&mut self) {
+        self.node = step(self.tree, self.node, true);
+    }
+
+    pub fn move_prev(&mut self) {
+        self.node = step(self.tree, self.node, false);
+    }
+
+    pub fn peek_next(&self) -> Option<(&'t K, &'t V)> {
+        let next = step(self.tree, self.node, true)?;
+        Some(unsafe { next.as_ref() }.pair_ref())
+    }
+
+    pub fn peek_prev(&self) -> Option<(&'t K, &'t V)> {
+        let prev = step(self.tree, self.node, false)?;
+        Some(unsafe { prev.as_ref() }.pair_ref())
+    }
+}
+
+/// Cursor, that can also insert and remove entries at its position
+pub struct CursorMut<'t, K, V, A = ()> {
+    tree: &'t mut BinTree<K, V, A>,
+    node: Option<NodePtr<K, V, A>>,
+}
+
+// SAFETY: the cursor is a unique borrow of the tree
+unsafe impl<'t, K: Send, V: Send, A: Send> Send for CursorMut<'t, K, V, A> {}
+unsafe impl<'t, K: Sync, V: Sync, A: Sync> Sync for CursorMut<'t, K, V, A> {}
+
+impl<'t, K, V, A> CursorMut<'t, K, V, A> {
+    pub(super) fn new(
+        tree: &'t mut BinTree<K, V, A>,
+        node: Option<NodePtr<K, V, A>>,
+    ) -> Self {
+        Self { tree, node }
+    }
+
+    /// Entry under the cursor, `None` at the ghost position
+    pub fn current(&self) -> Option<(&K, &V)> {
+        self.node.map(|node| unsafe { node.as_ref() }.pair_ref())
+    }
+
+    pub fn move_next(&mut self) {
+        self.node = step(self.tree, self.node, true);
+    }
+
+    pub fn move_prev(&mut self) {
+        self.node = step(self.tree, self.node, false);
+    }
+
+    pub fn peek_next(&self) -> Option<(&K, &V)> {
+        let next = step(self.tree, self.node, true)?;
+        Some(unsafe { next.as_ref() }.pair_ref())
+    }
+
+    pub fn peek_prev(&self) -> Option<(&K, &V)> {
+        let prev = step(self.tree, self.node, false)?;
+        Some(unsafe { prev.as_ref() }.pair_ref())
+    }
+
+    /// Read-only cursor at the same position, borrowing this one
+    pub fn as_cursor(&self) -> Cursor<'_, K, V, A> {
+        Cursor::new(self.tree, self.node)
+    }
+}
+
+/// Mutable access to values is only given without aggregates, see
+/// [`BinTree::find_mut`]
+impl<'t, K, V> CursorMut<'t, K, V> {
+    pub fn current_mut(&mut self) -> Option<(&K, &mut V)> {
+        self.node
+            .map(|mut node| unsafe { node.as_mut() }.pair_mut())
+    }
+}
+
+impl<'t, K: Ord, V, A: Monoid<K, V>> CursorMut<'t, K, V, A> {
+    /// Inserts an entry right after the cursor, or as the first one at the
+    /// ghost position, without searching for its place
+    ///
+    /// # Panics
+    /// If `key` does not fit between the current and the next entries.
+    pub fn insert_after(&mut self, key: K, value: V) {
+        self.insert_beside(key, value, true);
+    }
+
+    /// Inserts an entry right before the cursor, or as the last one at the
+    /// ghost position, without searching for its place
+    ///
+    /// # Panics
+    /// If `key` does not fit between the previous and the current entries.
+    pub fn insert_before(&mut self, key: K, value: V) {
+        self.insert_beside(key, value, false);
+    }
+
+    fn insert_beside(&mut self, key: K, value: V, after: bool) {
+        let (lo, hi) = match after {
+            true => (self.current(), self.peek_next()),
+            false => (self.peek_prev(), self.current()),
+        };
+        assert!(
+            lo.is_none_or(|(lo, _)| *lo < key)
+                && hi.is_none_or(|(hi, _)| key < *hi),
+            "key is out of order"
+        );
+
+        // the new node goes to the empty slot next to the current one: its
+        // own child, if missing, or the nearest child of its neighbour
+        let (near, far) = if after { (1, 0) } else { (0, 1) };
+        let p = match self.node {
+            Some(node) => match unsafe { node.as_ref() }.c[near] {
+                Some(_) => step(self.tree, Some(node), after),
+                None => Some(node),
+            },
+            None => step(self.tree, None, after),
+        };
+        let is_left = match (self.node, p) {
+            (Some(node), Some(p)) if node == p => near == 0,
+            _ => far == 0,
+        };
+        unsafe { self.tree.link(p, is_left, (key, value)) };
+    }
+
+    /// Removes the entry under the cursor, moving the cursor to the next one
+    pub fn remove_current(&mut self) -> Option<(K, V)> {
+        let node = self.node?;
+        self.node = unsafe { iter::next(node) };
+        Some(unsafe { self.tree.unlink(node) })
+    }
+}
+
+/// Iterator, that removes and yields entries, for which the predicate is
+/// `true`, see [`BinTree::extract_if`]
+pub struct ExtractIf<'t, K, V, F> {
+    cursor: CursorMut<'t, K, V>,
+    pred: F,
+}
+
+impl<'t, K, V, F> ExtractIf<'t, K, V, F> {
+    pub(super) fn new(cursor: CursorMut<'t, K, V>, pred: F) -> Self {
+        Self { cursor, pred }
+    }
+}
+
+impl<'t, K: Ord, V, F: FnMut(&K, &mut V) -> bool> Iterator
+    for ExtractIf<'t, K, V, F>
+{
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<(K, V)> {
+        loop {
+            let (key, value) = self.cursor.current_mut()?;
+            if (self.pred)(key, value) {
+                return self.cursor.remove_current();
+            }
+            self.cursor.move_next();
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use std::{
+    collections::HashMap,
+    fmt::{Debug, Write},
+};
+
+use super::{BinTree, Node};
+
+/// Escapes the characters, that are special in Graphviz record labels
+fn escape(text: impl Debug) -> String {
+    let text = format!("{:?}", text);
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '"' | '\\' | '{' | '}' | '|' | '<' | '>') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+impl<K: Debug, V: Debug, A> BinTree<K, V, A> {
+    /// Structure of the tree in the Graphviz `dot` language
+    ///
+    /// Every node shows its entry, the height `h`, the size `n` and the
+    /// balance factor `bf` of its subtree. Child links are solid, parent
+    /// links are dashed, a parent link to a node outside of the tree is
+    /// drawn red.
+    pub fn to_dot(&self) -> String {
+        let mut nodes = Vec::new();
+        let mut stack = self.root.as_deref().into_iter().collect::<Vec<_>>();
+        while let Some(node) = stack.pop() {
+            nodes.push(node);
+            stack.extend(node.c.iter().rev().flatten().map(|c| &**c));
+        }
+        let ids = nodes
+            .iter()
+            .enumerate()
+            .map(|(i, node)| (*node as *const Node<K, V, A>, i))
+            .collect::<HashMap<_, _>>();
+
+        let mut out = String::from("digraph {\n    node [shape=record];\n");
+        for (i, node) in nodes.iter().enumerate() {
+            let (key, value) = node.pair_ref();
+            let _ = writeln!(
+                out,
+                "    n{} [label=\"{{{} | {}}} | h={} n={} bf={}\"];",
+                i,
+                escape(key),
+                escape(value),
+                node.h,
+                node.n,
+                node.balance_factor(),
+            );
+            for (c, side) in node.c.iter().zip(["L", "R"]) {
+                if let Some(c) = c {
+                    let c = ids[&(&**c as *const Node<K, V, A>)];
+                    let _ =
+                        writeln!(out, "    n{} -> n{} [label={}];", i, c, side);
+                }
+            }
+            let Some(p) = node.p else {
+                continue;
+            };
+            let _ = match ids.get(&(p.as_ptr() as *const Node<K, V, A>)) {
+                Some(p) => writeln!(
+                    out,
+                    "    n{} -> n{} [style=dashed, color=gray];",
+                    i, p
+                ),
+                None => writeln!(
+                    out,
+                    "    p{0} [shape=point, color=red];\n    \
+                     n{0} -> p{0} [style=dashed, color=red];",
+                    i
+                ),
+            };
+        }
+        out.push_str("}\n");
+        out
+    }
+}
@@ -0,0 +1,149 @@
+use std::ptr::NonNull;
+
+use super::{BinTree, Link, Node};
+
+/// View into a single entry of [`BinTree`], found by [`BinTree::entry`]
+pub enum Entry<'t, K, V> {
+    Occupied(OccupiedEntry<'t, K, V>),
+    Vacant(VacantEntry<'t, K, V>),
+}
+
+pub struct OccupiedEntry<'t, K, V> {
+    tree: &'t mut BinTree<K, V>,
+    node: NonNull<Node<K, V>>,
+}
+
+/// Remembers the place, where a new node goes, so inserting does not search
+/// for it again
+pub struct VacantEntry<'t, K, V> {
+    tree: &'t mut BinTree<K, V>,
+    key: K,
+    p: Option<NonNull<Node<K, V>>>,
+    is_left: bool,
+}
+
+// SAFETY: the entries are unique borrows of the tree
+unsafe impl<'t, K: Send, V: Send> Send for OccupiedEntry<'t, K, V> {}
+unsafe impl<'t, K: Sync, V: Sync> Sync for OccupiedEntry<'t, K, V> {}
+unsafe impl<'t, K: Send, V: Send> Send for VacantEntry<'t, K, V> {}
+unsafe impl<'t, K: Sync, V: Sync> Sync for VacantEntry<'t, K, V> {}
+
+impl<'t, K: Ord, V> Entry<'t, K, V> {
+    /// Descends from the root to the node with `key` or to the empty slot
+    /// for it
+    pub(super) fn new(tree: &'t mut BinTree<K, V>, key: K) -> Self {
+        let mut p = None;
+        let mut is_left = false;
+        let mut cur = tree.root.as_ref().map(Link::ptr);
+        while let Some(ptr) = cur {
+            let node = unsafe { ptr.as_ref() };
+            let i = match key.cmp(&node.kv.0) {
+                std::cmp::Ordering::Less => 0,
+                std::cmp::Ordering::Greater => 1,
+                std::cmp::Ordering::Equal => {
+                    return Entry::Occupied(OccupiedEntry { tree, node: ptr })
+                }
+            };
+            (p, is_left) = (Some(ptr), i == 0);
+            cur = node.c[i].as_ref().map(Link::ptr);
+        }
+        Entry::Vacant(VacantEntry {
+            tree,
+            key,
+            p,
+            is_left,
+        })
+    }
+}
+
+impl<'t, K, V> Entry<'t, K, V> {
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key(),
+        }
+    }
+
+    pub fn or_insert(self, default: V) -> &'t mut V {
+        self.or_insert_with(|| default)
+    }
+
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'t mut V {
+        self.or_insert_with_key(|_| default())
+    }
+
+    pub fn or_insert_with_key<F: FnOnce(&K) -> V>(
+        self,
+        default: F,
+    ) -> &'t mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let value = default(entry.key());
+                entry.insert(value)
+            }
+        }
+    }
+
+    pub fn and_modify<F: FnOnce(&mut V)>(mut self, f: F) -> Self {
+        if let Entry::Occupied(entry) = &mut self {
+            f(entry.get_mut());
+        }
+        self
+    }
+}
+
+impl<'t, K, V: Default> Entry<'t, K, V> {
+    pub fn or_default(self) -> &'t mut V {
+        self.or_insert_with(V::default)
+    }
+}
+
+impl<'t, K, V> OccupiedEntry<'t, K, V> {
+    pub fn key(&self) -> &K {
+        &unsafe { self.node.as_ref() }.kv.0
+    }
+
+    pub fn get(&self) -> &V {
+        &unsafe { self.node.as_ref() }.kv.1
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut unsafe { self.node.as_mut() }.kv.1
+    }
+
+    pub fn into_mut(mut self) -> &'t mut V {
+        &mut unsafe { self.node.as_mut() }.kv.1
+    }
+
+    /// Replaces the value, returning the old one
+    pub fn insert(&mut self, value: V) -> V {
+        std::mem::replace(self.get_mut(), value)
+    }
+
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+
+    pub fn remove_entry(self) -> (K, V) {
+        unsafe { self.tree.unlink(self.node) }
+    }
+}
+
+impl<'t, K, V> VacantEntry<'t, K, V> {
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    /// Links a new node into the remembered slot and rebalances its
+    /// ancestors on the way up
+    pub fn insert(self, value: V) -> &'t mut V {
+        let kv = (self.key, value);
+        let mut node = unsafe { self.tree.link(self.p, self.is_left, kv) };
+        &mut unsafe { node.as_mut() }.kv.1
+    }
+}
@@ -0,0 +1,191 @@
+use std::ops::{Bound, Range};
+
+use super::{BinTree, Monoid, Node};
+
+/// Largest end of the intervals in a subtree, `None` for an empty one
+#[derive(Clone, Debug, PartialEq)]
+struct MaxEnd<K>(Option<K>);
+
+impl<K: Ord + Clone, V> Monoid<(K, K), V> for MaxEnd<K> {
+    #[inline]
+    fn empty() -> Self {
+        Self(None)
+    }
+
+    #[inline]
+    fn of((_, end): &(K, K), _: &V) -> Self {
+        Self(Some(end.clone()))
+    }
+
+    #[inline]
+    fn combine(&self, other: &Self) -> Self {
+        Self(self.0.clone().max(other.0.clone()))
+    }
+}
+
+type INode<K, V> = Node<(K, K), V, MaxEnd<K>>;
+
+/// Map from half-open intervals `start..end` to values, that finds the
+/// intervals overlapping a range or containing a point
+///
+/// Intervals are ordered by their starts, and every node keeps the largest
+/// end in its subtree, so queries skip subtrees, that end too early, and
+/// take `O(min(n, k log n))` for `k` intervals found.
+pub struct IntervalTree<K, V> {
+    tree: BinTree<(K, K), V, MaxEnd<K>>,
+}
+
+impl<K, V> Default for IntervalTree<K, V> {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            tree: BinTree::default(),
+        }
+    }
+}
+
+impl<K: Clone, V: Clone> Clone for IntervalTree<K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            tree: self.tree.clone(),
+        }
+    }
+}
+
+impl<K, V> IntervalTree<K, V> {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    /// Intervals in the order of their starts
+    pub fn iter(&self) -> impl Iterator<Item = (Range<&K>, &V)> + '_ {
+        self.tree.iter().map(|((start, end), v)| (start..end, v))
+    }
+}
+
+impl<K: Ord + Clone, V> IntervalTree<K, V> {
+    /// Inserts the interval, returning the old value of the same interval
+    ///
+    /// # Panics
+    /// If the interval is empty.
+    pub fn insert(&mut self, range: Range<K>, value: V) -> Option<V> {
+        assert!(range.start < range.end, "interval is empty");
+        let key = (range.start, range.end);
+        self.tree.insert(key, value).map(|(_, v)| v)
+    }
+
+    pub fn get(&self, range: &Range<K>) -> Option<&V> {
+        let key = (range.start.clone(), range.end.clone());
+        self.tree.find(&key).map(|(_, v)| v)
+    }
+
+    pub fn remove(&mut self, range: &Range<K>) -> Option<V> {
+        let key = (range.start.clone(), range.end.clone());
+        self.tree.remove(&key)
+    }
+
+    /// Intervals, that share at least one point with `range`, none for an
+    /// empty `range`
+    pub fn overlapping(&self, range: Range<K>) -> Overlapping<'_, K, V> {
+        Overlapping::new(&self.tree, range.start, Bound::Excluded(range.end))
+    }
+
+    /// Intervals, that contain `point`
+    pub fn containing(&self, point: K) -> Overlapping<'_, K, V> {
+        Overlapping::new(&self.tree, point.clone(), Bound::Included(point))
+    }
+
+    /// Whether any interval overlaps `range`, stopping at the first one
+    pub fn overlaps(&self, range: Range<K>) -> bool {
+        self.overlapping(range).next().is_some()
+    }
+
+    #[cfg(test)]
+    pub(crate) fn check(&self)
+    where
+        K: std::fmt::Debug,
+    {
+        self.tree.check();
+    }
+}
+
+/// Iterator over the intervals, that end after `lo` and start before `hi`,
+/// see [`IntervalTree::overlapping`]
+///
+/// In-order walk, that does not descend into subtrees ending at or before
+/// `lo`, and stops at the first interval starting after `hi`.
+pub struct Overlapping<'t, K, V> {
+    stack: Vec<&'t INode<K, V>>,
+    lo: K,
+    hi: Bound<K>,
+}
+
+impl<'t, K: Ord, V> Overlapping<'t, K, V> {
+    fn new(
+        tree: &'t BinTree<(K, K), V, MaxEnd<K>>,
+        lo: K,
+        hi: Bound<K>,
+    ) -> Self {
+        let mut this = Self {
+            stack: Vec::new(),
+            lo,
+            hi,
+        };
+        // an empty `lo..hi` shares no point with any interval
+        if this.starts_before_hi(&this.lo) {
+            this.push_left(tree.root.as_deref());
+        }
+        this
+    }
+
+    fn push_left(&mut self, mut cur: Option<&'t INode<K, V>>) {
+        while let Some(node) = cur {
+            if node.a.0.as_ref().is_none_or(|end| *end <= self.lo) {
+                break;
+            }
+            self.stack.push(node);
+            cur = node.c[0].as_deref();
+        }
+    }
+
+    fn starts_before_hi(&self, start: &K) -> bool {
+        match &self.hi {
+            Bound::Included(hi) => start <= hi,
+            Bound::Excluded(hi) => start < hi,
+            Bound::Unbounded => true,
+        }
+    }
+}
+
+impl<'t, K: Ord, V> Iterator for Overlapping<'t, K, V> {
+    type Item = (Range<&'t K>, &'t V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(node) = self.stack.pop() {
+            let ((start, end), value) = &node.kv;
+            if !self.starts_before_hi(start) {
+                // all of the following intervals start even later
+                self.stack.clear();
+                break;
+            }
+            self.push_left(node.c[1].as_deref());
+            if *end > self.lo {
+                return Some((start..end, value));
+            }
+        }
+        None
+    }
+}
+
+impl<'t, K: Ord, V> std::iter::FusedIterator for Overlapping<'t, K, V> {}
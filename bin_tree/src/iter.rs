@@ -1,185 +1,312 @@
-use std::{marker::PhantomData, ptr::NonNull};
+use std::{
+    marker::PhantomData,
+    ops::Bound,
+    ptr::{addr_of_mut, NonNull},
+};
 
-#[derive(Clone, Copy)]
-enum IterPhase {
-    Left,
-    PostLeft,
-    Right,
-    PostRight,
+use super::{Link, Monoid, Node};
+
+type NodePtr<K, V, A> = NonNull<Node<K, V, A>>;
+
+// The walkers below read single fields through raw pointers and never borrow
+// a whole node, as a `&mut V` handed out by `IterMut` may point into it.
+
+#[inline]
+unsafe fn child<K, V, A>(
+    ptr: NodePtr<K, V, A>,
+    i: usize,
+) -> Option<NodePtr<K, V, A>> {
+    unsafe { (*ptr.as_ptr()).c[i].as_ref().map(Link::ptr) }
 }
 
-struct RawIter<K, V> {
-    fwd: Option<NonNull<super::Node<K, V>>>,
-    bck: Option<NonNull<super::Node<K, V>>>,
-    phases: [IterPhase; 2],
+#[inline]
+unsafe fn parent<K, V, A>(ptr: NodePtr<K, V, A>) -> Option<NodePtr<K, V, A>> {
+    unsafe { (*ptr.as_ptr()).p }
 }
 
-impl<K, V> RawIter<K, V> {
-    #[inline]
-    fn new(ptr: Option<NonNull<super::Node<K, V>>>) -> Self {
-        let mut fwd = ptr;
-        while let Some(ptr) =
-            fwd.and_then(|mut fwd| unsafe { fwd.as_mut() }.c[0].as_deref_mut())
-        {
-            fwd = Some(NonNull::from(ptr));
-        }
-        let mut bck = ptr;
-        while let Some(ptr) =
-            bck.and_then(|mut bck| unsafe { bck.as_mut() }.c[1].as_deref_mut())
-        {
-            bck = Some(NonNull::from(ptr));
-        }
-        Self {
-            fwd,
-            bck,
-            phases: [IterPhase::Left, IterPhase::Right],
+#[inline]
+unsafe fn size<K, V, A>(ptr: Option<NodePtr<K, V, A>>) -> usize {
+    ptr.map_or(0, |ptr| unsafe { (*ptr.as_ptr()).n })
+}
+
+/// Keys are never given out mutably, so a shared borrow is fine
+#[inline]
+unsafe fn key<'n, K, V, A>(ptr: NodePtr<K, V, A>) -> &'n K {
+    unsafe { &(*ptr.as_ptr()).kv.0 }
+}
+
+/// Leftmost node of the subtree
+pub(super) unsafe fn first<K, V, A>(
+    mut ptr: NodePtr<K, V, A>,
+) -> NodePtr<K, V, A> {
+    while let Some(l) = unsafe { child(ptr, 0) } {
+        ptr = l;
+    }
+    ptr
+}
+
+/// Rightmost node of the subtree
+pub(super) unsafe fn last<K, V, A>(
+    mut ptr: NodePtr<K, V, A>,
+) -> NodePtr<K, V, A> {
+    while let Some(r) = unsafe { child(ptr, 1) } {
+        ptr = r;
+    }
+    ptr
+}
+
+/// In-order successor, walking up through parent pointers if needed
+pub(super) unsafe fn next<K, V, A>(
+    ptr: NodePtr<K, V, A>,
+) -> Option<NodePtr<K, V, A>> {
+    if let Some(r) = unsafe { child(ptr, 1) } {
+        return Some(unsafe { first(r) });
+    }
+    let mut from = ptr;
+    let mut p = unsafe { parent(ptr) }?;
+    while unsafe { child(p, 1) } == Some(from) {
+        from = p;
+        p = unsafe { parent(p) }?;
+    }
+    Some(p)
+}
+
+/// In-order predecessor, walking up through parent pointers if needed
+pub(super) unsafe fn prev<K, V, A>(
+    ptr: NodePtr<K, V, A>,
+) -> Option<NodePtr<K, V, A>> {
+    if let Some(l) = unsafe { child(ptr, 0) } {
+        return Some(unsafe { last(l) });
+    }
+    let mut from = ptr;
+    let mut p = unsafe { parent(ptr) }?;
+    while unsafe { child(p, 0) } == Some(from) {
+        from = p;
+        p = unsafe { parent(p) }?;
+    }
+    Some(p)
+}
+
+/// Position of the node in the whole tree and the root of the tree
+unsafe fn rank<K, V, A>(ptr: NodePtr<K, V, A>) -> (usize, NodePtr<K, V, A>) {
+    let mut rank = unsafe { size(child(ptr, 0)) };
+    let (mut from, mut cur) = (ptr, unsafe { parent(ptr) });
+    while let Some(p) = cur {
+        if unsafe { child(p, 1) } == Some(from) {
+            rank += unsafe { size(child(p, 0)) } + 1;
         }
+        (from, cur) = (p, unsafe { parent(p) });
     }
+    (rank, from)
+}
 
-    unsafe fn next_fwd(&mut self) -> Option<(NonNull<K>, NonNull<V>)> {
-        loop {
-            let s = unsafe { self.fwd?.as_mut() };
-
-            if let IterPhase::Left = self.phases[0] {
-                if let Some(c0) = s.c[0].as_deref_mut() {
-                    self.fwd = Some(NonNull::from(c0));
-                    self.phases[0] = IterPhase::Left;
-                    continue;
-                } else {
-                    self.phases[0] = IterPhase::PostLeft;
-                }
+/// Node with `i` smaller keys in the subtree
+unsafe fn select<K, V, A>(
+    mut ptr: NodePtr<K, V, A>,
+    mut i: usize,
+) -> NodePtr<K, V, A> {
+    loop {
+        let l = unsafe { size(child(ptr, 0)) };
+        let c = match i.cmp(&l) {
+            std::cmp::Ordering::Less => 0,
+            std::cmp::Ordering::Equal => return ptr,
+            std::cmp::Ordering::Greater => {
+                i -= l + 1;
+                1
             }
+        };
+        ptr = unsafe { child(ptr, c) }.expect("index is within subtree");
+    }
+}
 
-            if let IterPhase::PostLeft = self.phases[0] {
-                if self.fwd == self.bck {
-                    return None;
-                } else {
-                    self.phases[0] = IterPhase::Right;
-                    let (k, v) = &mut s.kv;
-                    return Some((NonNull::from(k), NonNull::from(v)));
-                }
-            }
+/// First node of the subtree, whose key is above the lower `bound`
+pub(super) unsafe fn lower<K: Ord, V, A>(
+    mut cur: Option<NodePtr<K, V, A>>,
+    bound: Bound<&K>,
+) -> Option<NodePtr<K, V, A>> {
+    let mut found = None;
+    while let Some(ptr) = cur {
+        let k = unsafe { key(ptr) };
+        let above = match bound {
+            Bound::Included(key) => k >= key,
+            Bound::Excluded(key) => k > key,
+            Bound::Unbounded => true,
+        };
+        let i = if above {
+            found = Some(ptr);
+            0
+        } else {
+            1
+        };
+        cur = unsafe { child(ptr, i) };
+    }
+    found
+}
 
-            if let IterPhase::Right = self.phases[0] {
-                if let Some(c1) = s.c[1].as_deref_mut() {
-                    self.fwd = Some(NonNull::from(c1));
-                    self.phases[0] = IterPhase::Left;
-                    continue;
-                } else {
-                    self.phases[0] = IterPhase::PostRight;
-                }
-            }
+/// Last node of the subtree, whose key is below the upper `bound`
+unsafe fn upper<K: Ord, V, A>(
+    mut cur: Option<NodePtr<K, V, A>>,
+    bound: Bound<&K>,
+) -> Option<NodePtr<K, V, A>> {
+    let mut found = None;
+    while let Some(ptr) = cur {
+        let k = unsafe { key(ptr) };
+        let below = match bound {
+            Bound::Included(key) => k <= key,
+            Bound::Excluded(key) => k < key,
+            Bound::Unbounded => true,
+        };
+        let i = if below {
+            found = Some(ptr);
+            1
+        } else {
+            0
+        };
+        cur = unsafe { child(ptr, i) };
+    }
+    found
+}
 
-            if let IterPhase::PostRight = self.phases[0] {
-                let p = unsafe { (*s).p?.as_mut() };
-                if self.fwd == p.c[0].as_deref().map(NonNull::from) {
-                    self.phases[0] = IterPhase::PostLeft;
-                } else {
-                    self.phases[0] = IterPhase::PostRight;
-                }
-                self.fwd = Some(NonNull::from(p));
-            }
+/// Raw pointers to the key and the value, shared iterators must not go
+/// through `&mut` to get them
+#[inline]
+unsafe fn fields<K, V, A>(ptr: NodePtr<K, V, A>) -> (NonNull<K>, NonNull<V>) {
+    let kv = unsafe { addr_of_mut!((*ptr.as_ptr()).kv) };
+    unsafe {
+        (
+            NonNull::new_unchecked(addr_of_mut!((*kv).0)),
+            NonNull::new_unchecked(addr_of_mut!((*kv).1)),
+        )
+    }
+}
+
+/// Pair of cursors, that walk towards each other
+///
+/// Both `fwd` and `bck` are yet to be yielded, `len` counts the nodes
+/// between them, inclusive.
+struct RawIter<K, V, A> {
+    fwd: Option<NodePtr<K, V, A>>,
+    bck: Option<NodePtr<K, V, A>>,
+    len: usize,
+}
+
+impl<K, V, A> RawIter<K, V, A> {
+    #[inline]
+    fn new(ptr: Option<NodePtr<K, V, A>>) -> Self {
+        Self {
+            fwd: ptr.map(|ptr| unsafe { first(ptr) }),
+            bck: ptr.map(|ptr| unsafe { last(ptr) }),
+            len: unsafe { size(ptr) },
         }
     }
 
-    unsafe fn next_bck(&mut self) -> Option<(NonNull<K>, NonNull<V>)> {
-        loop {
-            let s = unsafe { self.bck?.as_mut() };
-
-            if let IterPhase::Right = self.phases[1] {
-                if let Some(c0) = s.c[1].as_deref_mut() {
-                    self.bck = Some(NonNull::from(c0));
-                    self.phases[1] = IterPhase::Right;
-                    continue;
-                } else {
-                    self.phases[1] = IterPhase::PostRight;
-                }
-            }
+    #[inline]
+    fn empty() -> Self {
+        Self {
+            fwd: None,
+            bck: None,
+            len: 0,
+        }
+    }
 
-            if let IterPhase::PostRight = self.phases[1] {
-                if false {
-                    return None;
-                } else {
-                    self.phases[1] = IterPhase::Left;
-                    let (k, v) = &mut s.kv;
-                    return Some((NonNull::from(k), NonNull::from(v)));
-                }
-            }
+    unsafe fn next_fwd(&mut self) -> Option<(NonNull<K>, NonNull<V>)> {
+        let ptr = self.fwd?;
+        self.len -= 1;
+        if self.len == 0 {
+            *self = Self::empty();
+        } else {
+            self.fwd = unsafe { next(ptr) };
+        }
+        Some(unsafe { fields(ptr) })
+    }
 
-            if let IterPhase::Left = self.phases[1] {
-                if let Some(c1) = s.c[0].as_deref_mut() {
-                    self.bck = Some(NonNull::from(c1));
-                    self.phases[1] = IterPhase::Right;
-                    continue;
-                } else {
-                    self.phases[1] = IterPhase::PostLeft;
-                }
-            }
+    unsafe fn next_bck(&mut self) -> Option<(NonNull<K>, NonNull<V>)> {
+        let ptr = self.bck?;
+        self.len -= 1;
+        if self.len == 0 {
+            *self = Self::empty();
+        } else {
+            self.bck = unsafe { prev(ptr) };
+        }
+        Some(unsafe { fields(ptr) })
+    }
 
-            if let IterPhase::PostLeft = self.phases[1] {
-                let p = unsafe { (*s).p?.as_mut() };
-                if self.bck == p.c[0].as_deref().map(NonNull::from) {
-                    self.phases[1] = IterPhase::PostLeft;
-                } else {
-                    self.phases[1] = IterPhase::PostRight;
-                }
-                self.bck = Some(NonNull::from(p));
+    /// Skips `n` nodes from the front in `O(log n)`
+    fn skip_fwd(&mut self, n: usize) {
+        match self.fwd {
+            Some(_) if n >= self.len => *self = Self::empty(),
+            Some(fwd) if n > 0 => {
+                let (rank, root) = unsafe { rank(fwd) };
+                self.fwd = Some(unsafe { select(root, rank + n) });
+                self.len -= n;
             }
+            _ => {}
         }
     }
 
-    unsafe fn last(self) -> Option<(NonNull<K>, NonNull<V>)> {
-        let mut s = self.fwd?.as_mut();
-        while let Some(mut p) = s.p {
-            s = unsafe { p.as_mut() };
+    /// Skips `n` nodes from the back in `O(log n)`
+    fn skip_bck(&mut self, n: usize) {
+        match self.bck {
+            Some(_) if n >= self.len => *self = Self::empty(),
+            Some(bck) if n > 0 => {
+                let (rank, root) = unsafe { rank(bck) };
+                self.bck = Some(unsafe { select(root, rank - n) });
+                self.len -= n;
+            }
+            _ => {}
         }
+    }
+}
 
-        while let Some(r) = s.c[1].as_deref_mut() {
-            s = r;
+impl<K: Ord, V, A> RawIter<K, V, A> {
+    /// Cursors at the ends of `(start, end)` bounds, empty if nothing is
+    /// in between
+    fn range(
+        ptr: Option<NodePtr<K, V, A>>,
+        (start, end): (Bound<&K>, Bound<&K>),
+    ) -> Self {
+        let fwd = unsafe { lower(ptr, start) };
+        let bck = unsafe { upper(ptr, end) };
+        match (fwd, bck) {
+            (Some(f), Some(b)) if unsafe { key(f) <= key(b) } => {
+                let len = unsafe { rank(b).0 - rank(f).0 + 1 };
+                Self { fwd, bck, len }
+            }
+            _ => Self::empty(),
         }
-
-        let (k, v) = &mut s.kv;
-        Some((NonNull::from(k), NonNull::from(v)))
     }
 }
 
-impl<K, V> Clone for RawIter<K, V> {
+impl<K, V, A> Clone for RawIter<K, V, A> {
     fn clone(&self) -> Self {
-        Self {
-            fwd: self.fwd,
-            bck: self.bck,
-            phases: self.phases,
-        }
+        *self
     }
 }
 
-impl<K, V> Copy for RawIter<K, V> {}
+impl<K, V, A> Copy for RawIter<K, V, A> {}
 
 macro_rules! iter_impl {
     (ref $Name:ident, $Item:ty, $toItem:expr) => {
         iter_impl! { mut $Name, $Item, $toItem }
 
-        impl<'n, K, V> Clone for $Name<'n, K, V> {
+        impl<'n, K, V, A> Clone for $Name<'n, K, V, A> {
             fn clone(&self) -> Self {
-                Self {
-                    raw: self.raw,
-                    _phantom: PhantomData,
-                }
+                *self
             }
         }
 
-        impl<'n, K, V> Copy for $Name<'n, K, V> {}
+        impl<'n, K, V, A> Copy for $Name<'n, K, V, A> {}
     };
     (mut $Name:ident, $Item:ty, $toItem:expr) => {
         #[repr(transparent)]
-        pub struct $Name<'n, K, V> {
-            raw: RawIter<K, V>,
+        pub struct $Name<'n, K, V, A = ()> {
+            raw: RawIter<K, V, A>,
             _phantom: PhantomData<$Item>,
         }
 
-        impl<'n, K, V> $Name<'n, K, V> {
+        impl<'n, K, V, A> $Name<'n, K, V, A> {
             #[allow(dead_code)]
-            pub(super) fn new(ptr: Option<NonNull<super::Node<K, V>>>) -> Self {
+            pub(super) fn new(ptr: Option<NodePtr<K, V, A>>) -> Self {
                 Self {
                     raw: RawIter::new(ptr),
                     _phantom: PhantomData,
@@ -187,7 +314,20 @@ macro_rules! iter_impl {
             }
         }
 
-        impl<'n, K, V> Iterator for $Name<'n, K, V> {
+        impl<'n, K: Ord, V, A> $Name<'n, K, V, A> {
+            #[allow(dead_code)]
+            pub(super) fn range(
+                ptr: Option<NodePtr<K, V, A>>,
+                bounds: (Bound<&K>, Bound<&K>),
+            ) -> Self {
+                Self {
+                    raw: RawIter::range(ptr, bounds),
+                    _phantom: PhantomData,
+                }
+            }
+        }
+
+        impl<'n, K, V, A> Iterator for $Name<'n, K, V, A> {
             type Item = $Item;
 
             fn next(&mut self) -> Option<$Item> {
@@ -195,27 +335,53 @@ macro_rules! iter_impl {
                 Some(unsafe { $toItem(k, v) })
             }
 
-            fn last(self) -> Option<$Item> {
-                let (k, v) = unsafe { self.raw.last() }?;
-                Some(unsafe { $toItem(k, v) })
+            fn last(mut self) -> Option<$Item> {
+                self.next_back()
             }
 
-            fn min(mut self) -> Option<$Item> {
+            fn size_hint(&self) -> (usize, Option<usize>) {
+                (self.raw.len, Some(self.raw.len))
+            }
+
+            fn count(self) -> usize {
+                self.raw.len
+            }
+
+            fn nth(&mut self, n: usize) -> Option<$Item> {
+                self.raw.skip_fwd(n);
                 self.next()
             }
+        }
 
-            fn max(self) -> Option<$Item> {
-                self.last()
+        impl<'n, K, V, A> DoubleEndedIterator for $Name<'n, K, V, A> {
+            fn next_back(&mut self) -> Option<$Item> {
+                let (k, v) = unsafe { self.raw.next_bck() }?;
+                Some(unsafe { $toItem(k, v) })
+            }
+
+            fn nth_back(&mut self, n: usize) -> Option<$Item> {
+                self.raw.skip_bck(n);
+                self.next_back()
             }
         }
-    };
-}
 
-impl<'n, K, V> DoubleEndedIterator for Iter<'n, K, V> {
-    fn next_back(&mut self) -> Option<Self::Item> {
-        let (k, v) = unsafe { self.raw.next_bck() }?;
-        Some(unsafe { (k.as_ref(), v.as_ref()) })
-    }
+        impl<'n, K, V, A> ExactSizeIterator for $Name<'n, K, V, A> {}
+
+        impl<'n, K, V, A> std::iter::FusedIterator for $Name<'n, K, V, A> {}
+
+        // SAFETY: the iterator is a borrow of the tree, that gives out only
+        // the items, and the walk reads the links shared with other
+        // iterators
+        unsafe impl<'n, K, V, A: Sync> Send for $Name<'n, K, V, A> where
+            $Item: Send
+        {
+        }
+
+        unsafe impl<'n, K, V, A: Sync> Sync for $Name<'n, K, V, A> where
+            $Item: Sync
+        {
+        }
+    };
 }
 
 iter_impl! { ref Iter, (&'n K, &'n V), |k: NonNull<K>, v: NonNull<V>| (k.as_ref(), v.as_ref()) }
@@ -224,3 +390,40 @@ iter_impl! { ref Values, &'n V, |_, v: NonNull<V>| v.as_ref() }
 
 iter_impl! { mut IterMut, (&'n K, &'n mut V), |k: NonNull<K>, mut v: NonNull<V>| (k.as_ref(), v.as_mut()) }
 iter_impl! { mut ValuesMut, &'n mut V, |_, mut v: NonNull<V>| v.as_mut() }
+
+/// Owning iterator, that moves entries out of the tree
+pub struct IntoIter<K, V, A = ()> {
+    nodes: std::vec::IntoIter<Link<Node<K, V, A>>>,
+}
+
+impl<K, V, A: Monoid<K, V>> IntoIter<K, V, A> {
+    pub(super) fn new(root: Option<Link<Node<K, V, A>>>) -> Self {
+        let mut nodes = Vec::new();
+        Node::flatten(root, &mut nodes);
+        Self {
+            nodes: nodes.into_iter(),
+        }
+    }
+}
+
+impl<K, V, A> Iterator for IntoIter<K, V, A> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<(K, V)> {
+        self.nodes.next().map(|node| node.pair())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.nodes.size_hint()
+    }
+}
+
+impl<K, V, A> DoubleEndedIterator for IntoIter<K, V, A> {
+    fn next_back(&mut self) -> Option<(K, V)> {
+        self.nodes.next_back().map(|node| node.pair())
+    }
+}
+
+impl<K, V, A> ExactSizeIterator for IntoIter<K, V, A> {}
+
+impl<K, V, A> std::iter::FusedIterator for IntoIter<K, V, A> {}
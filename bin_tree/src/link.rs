@@ -0,0 +1,67 @@
+use std::{
+    mem::ManuallyDrop,
+    ops::{Deref, DerefMut},
+    ptr::NonNull,
+};
+
+/// Owning pointer to a heap allocated node, a replacement for `Box`
+///
+/// Moving a `Box` asserts, that it is the only pointer to its allocation,
+/// which invalidates the parent pointers of the children and the positions
+/// of iterators and cursors. `Link` is a plain pointer, all of the raw
+/// pointers to the node are copies of it, so they stay valid while the node
+/// is moved around the tree.
+pub(crate) struct Link<T>(NonNull<T>);
+
+impl<T> Link<T> {
+    #[inline]
+    pub(crate) fn new(value: T) -> Self {
+        let ptr = Box::into_raw(Box::new(value));
+        // SAFETY: `Box` is never null
+        Self(unsafe { NonNull::new_unchecked(ptr) })
+    }
+
+    /// Pointer with the same provenance as the link itself
+    #[inline]
+    pub(crate) fn ptr(&self) -> NonNull<T> {
+        self.0
+    }
+
+    #[inline]
+    pub(crate) fn into_inner(self) -> T {
+        let this = ManuallyDrop::new(self);
+        // SAFETY: allocated by `Box` in `new` and not freed, as `this` is
+        // not dropped
+        *unsafe { Box::from_raw(this.0.as_ptr()) }
+    }
+}
+
+impl<T> Deref for Link<T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        // SAFETY: the link owns a valid allocation, borrowing the link
+        // borrows the node
+        unsafe { self.0.as_ref() }
+    }
+}
+
+impl<T> DerefMut for Link<T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: as for `deref`, mutable borrow of the link is unique
+        unsafe { self.0.as_mut() }
+    }
+}
+
+impl<T> Drop for Link<T> {
+    fn drop(&mut self) {
+        // SAFETY: allocated by `Box` in `new`, dropped only once
+        drop(unsafe { Box::from_raw(self.0.as_ptr()) });
+    }
+}
+
+// SAFETY: `Link` owns its node the same way `Box` does
+unsafe impl<T: Send> Send for Link<T> {}
+unsafe impl<T: Sync> Sync for Link<T> {}
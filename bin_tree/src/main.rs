@@ -1,78 +1,403 @@
-use std::{cmp::Ordering, fmt::Debug, iter::FromIterator, ptr::NonNull};
+use std::{
+    cmp::Ordering,
+    fmt::Debug,
+    hash::{Hash, Hasher},
+    iter::FromIterator,
+    ops::{Bound, RangeBounds},
+    ptr::NonNull,
+};
 
+#[cfg(feature = "serde")]
+use serde::{
+    de::{MapAccess, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+mod cursor;
+mod dot;
+mod entry;
+mod interval;
 mod iter;
+mod link;
+mod monoid;
+mod persistent;
+
+pub use interval::IntervalTree;
+use link::Link;
+pub use monoid::Monoid;
+pub use persistent::PersistentTree;
 
-#[derive(Clone, Hash, PartialEq, Eq)]
-struct Node<K, V> {
+struct Node<K, V, A = ()> {
     kv: (K, V),
     c: [Ref<Self>; 2],
+    /// Parent, a copy of the link, that owns this node
     p: Option<NonNull<Self>>,
+    /// Height of the subtree, leaf has height 1
+    h: u8,
+    /// Number of nodes in the subtree
+    n: usize,
+    /// Summary of the subtree entries
+    a: A,
 }
 
-impl<K: Debug, V: Debug> Debug for Node<K, V> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut s = f.debug_struct("Node");
-        s.field("key", &self.kv.0).field("value", &self.kv.1);
-        if let Some(c0) = self.c[0].as_deref() {
-            s.field("l", c0);
-        }
-        if let Some(c1) = self.c[1].as_deref() {
-            s.field("r", c1);
+// SAFETY: nodes are owned through `c`, `p` is only followed while the tree
+// is borrowed, so sharing and sending works as for the entries themselves
+unsafe impl<K: Send, V: Send, A: Send> Send for Node<K, V, A> {}
+unsafe impl<K: Sync, V: Sync, A: Sync> Sync for Node<K, V, A> {}
+
+type Ref<T> = Option<Link<T>>;
+
+impl<K, V, A> Node<K, V, A> {
+    fn pair_ref(&self) -> (&K, &V) {
+        let (k, v) = &self.kv;
+        (k, v)
+    }
+
+    fn pair_mut(&mut self) -> (&K, &mut V) {
+        let (k, v) = &mut self.kv;
+        (&*k, v)
+    }
+
+    #[inline]
+    fn height(this: &Ref<Self>) -> u8 {
+        this.as_ref().map_or(0, |this| this.h)
+    }
+
+    /// Reads only the `n` field, iterators use it next to handed out
+    /// references to values
+    #[inline]
+    fn size(this: &Ref<Self>) -> usize {
+        this.as_ref()
+            .map_or(0, |this| unsafe { (*this.ptr().as_ptr()).n })
+    }
+
+    /// Difference between heights of the left and the right subtrees
+    #[inline]
+    fn balance_factor(&self) -> i16 {
+        Self::height(&self.c[0]) as i16 - Self::height(&self.c[1]) as i16
+    }
+}
+
+impl<K: Clone, V: Clone, A: Clone> Node<K, V, A> {
+    /// Deep copy of the subtree, parent pointers of which lead into the
+    /// copy rather than into the original
+    fn clone_subtree(&self) -> Link<Self> {
+        let mut this = Link::new(Node {
+            kv: self.kv.clone(),
+            c: [None, None],
+            p: None,
+            h: self.h,
+            n: self.n,
+            a: self.a.clone(),
+        });
+        let p = this.ptr();
+        for (c, orig) in this.c.iter_mut().zip(&self.c) {
+            *c = orig.as_deref().map(|orig| {
+                let mut c = orig.clone_subtree();
+                c.p = Some(p);
+                c
+            });
         }
-        s.finish()
+        this
+    }
+}
+
+impl<K, V, A> Link<Node<K, V, A>> {
+    fn pair(self) -> (K, V) {
+        self.into_inner().kv
     }
 }
 
-type Ref<T> = Option<Box<T>>;
+/// Structural changes need the owning link of a node, as its children point
+/// to it with copies of that link
+impl<K, V, A: Monoid<K, V>> Link<Node<K, V, A>> {
+    /// Recalculates subtree metadata from the children and points them back
+    /// to `self`, must be called after any change of the children
+    fn fix(&mut self) {
+        let p = self.ptr();
+        for c in self.c.iter_mut().flatten() {
+            c.p = Some(p);
+        }
+        self.h = 1 + Node::height(&self.c[0]).max(Node::height(&self.c[1]));
+        self.n = 1 + Node::size(&self.c[0]) + Node::size(&self.c[1]);
+        let a = A::of(&self.kv.0, &self.kv.1);
+        self.a = match &self.c {
+            [Some(l), Some(r)] => l.a.combine(&a).combine(&r.a),
+            [Some(l), None] => l.a.combine(&a),
+            [None, Some(r)] => a.combine(&r.a),
+            [None, None] => a,
+        };
+    }
+
+    fn set_child(&mut self, is_left: bool, c: Ref<Node<K, V, A>>) {
+        if is_left {
+            self.c[0] = c;
+        } else {
+            self.c[1] = c;
+        }
+        self.fix();
+    }
 
-impl<K, V> Node<K, V> {
+    fn rotate_left(&mut self) {
+        let Some(b) = self.c[1].take() else {
+            return;
+        };
+        let mut a = std::mem::replace(self, b);
+        self.p = a.p;
+        let c = self.c[0].take();
+        a.set_child(false, c);
+        self.set_child(true, Some(a));
+    }
+
+    fn rotate_right(&mut self) {
+        let Some(b) = self.c[0].take() else {
+            return;
+        };
+        let mut a = std::mem::replace(self, b);
+        self.p = a.p;
+        let c = self.c[1].take();
+        a.set_child(true, c);
+        self.set_child(false, Some(a));
+    }
+
+    /// Restores AVL invariant of `self`, given that both subtrees are
+    /// balanced and their heights differ at most by 2
+    fn rebalance(&mut self) {
+        self.fix();
+        match self.balance_factor() {
+            2.. => {
+                if let Some(l) = &mut self.c[0] {
+                    if l.balance_factor() < 0 {
+                        l.rotate_left();
+                    }
+                }
+                self.rotate_right();
+            }
+            ..=-2 => {
+                if let Some(r) = &mut self.c[1] {
+                    if r.balance_factor() > 0 {
+                        r.rotate_right();
+                    }
+                }
+                self.rotate_left();
+            }
+            _ => {}
+        }
+    }
+}
+
+impl<K, V, A: Monoid<K, V>> Node<K, V, A> {
     #[inline]
     fn root_p(kv: (K, V), p: Option<NonNull<Self>>) -> Self {
         Self {
+            a: A::of(&kv.0, &kv.1),
             kv,
             c: [None, None],
             p,
+            h: 1,
+            n: 1,
         }
     }
 
-    fn box_pair(self: Box<Self>) -> (K, V) {
-        self.kv
+    /// Concatenates `l`, `mid` and `r`, all keys of `l` must be less than
+    /// the key of `mid` and all keys of `r` must be greater
+    ///
+    /// Takes `O(|height(l) - height(r)|)`.
+    fn join(l: Ref<Self>, mut mid: Link<Self>, r: Ref<Self>) -> Link<Self> {
+        let (hl, hr) = (Self::height(&l), Self::height(&r));
+        match (l, r) {
+            (Some(mut l), r) if hl > hr + 1 => {
+                let lr = l.c[1].take();
+                l.c[1] = Some(Self::join(lr, mid, r));
+                l.rebalance();
+                l
+            }
+            (l, Some(mut r)) if hr > hl + 1 => {
+                let rl = r.c[0].take();
+                r.c[0] = Some(Self::join(l, mid, rl));
+                r.rebalance();
+                r
+            }
+            (l, r) => {
+                mid.c = [l, r];
+                mid.fix();
+                mid
+            }
+        }
     }
 
-    #[allow(dead_code)]
-    fn pair(self) -> (K, V) {
-        self.kv
+    /// Concatenates `l` and `r`, all keys of `l` must be less than the keys
+    /// of `r`
+    fn join2(l: Ref<Self>, mut r: Ref<Self>) -> Ref<Self> {
+        match Self::remove_edge(&mut r, true) {
+            Some(mid) => Some(Self::join(l, mid, r)),
+            None => l,
+        }
     }
 
-    fn pair_ref(&self) -> (&K, &V) {
-        let (k, v) = &self.kv;
-        (k, v)
+    /// Detaches the leftmost or the rightmost node of the subtree
+    fn remove_edge(maybe_this: &mut Ref<Self>, is_left: bool) -> Ref<Self> {
+        let (near, far) = if is_left { (0, 1) } else { (1, 0) };
+        let this = maybe_this.as_mut()?;
+        if this.c[near].is_some() {
+            let edge = Self::remove_edge(&mut this.c[near], is_left);
+            this.rebalance();
+            edge
+        } else {
+            let rest = this.c[far].take();
+            let mut edge = std::mem::replace(maybe_this, rest)?;
+            if let Some(rest) = maybe_this {
+                rest.p = edge.p;
+            }
+            edge.p = None;
+            edge.fix();
+            Some(edge)
+        }
     }
 
-    fn pair_mut(&mut self) -> (&K, &mut V) {
-        let (k, v) = &mut self.kv;
-        (&*k, v)
+    /// Moves all nodes of the subtree to `out` in order, detaching them
+    fn flatten(this: Ref<Self>, out: &mut Vec<Link<Self>>) {
+        let mut stack = Vec::new();
+        let mut cur = this;
+        loop {
+            while let Some(mut node) = cur {
+                cur = node.c[0].take();
+                stack.push(node);
+            }
+            let Some(mut node) = stack.pop() else {
+                break;
+            };
+            cur = node.c[1].take();
+            node.p = None;
+            node.fix();
+            out.push(node);
+        }
+    }
+
+    /// Builds perfectly balanced subtree from the first `len` of ordered
+    /// detached `nodes`
+    fn build(
+        nodes: &mut impl Iterator<Item = Link<Self>>,
+        len: usize,
+    ) -> Ref<Self> {
+        if len == 0 {
+            return None;
+        }
+        let l = Self::build(nodes, len / 2);
+        let mut mid = nodes.next()?;
+        let r = Self::build(nodes, len - len / 2 - 1);
+        mid.c = [l, r];
+        mid.fix();
+        Some(mid)
+    }
+
+    /// Detaches `this` from the tree, putting the rest of its subtree in
+    /// its place
+    fn remove_this(maybe_this: &mut Ref<Self>) -> Ref<Self> {
+        let this = maybe_this.as_mut()?;
+        let Some(mut next) = Self::remove_edge(&mut this.c[1], true) else {
+            let l = this.c[0].take();
+            let mut this = std::mem::replace(maybe_this, l)?;
+            if let Some(l) = maybe_this {
+                l.p = this.p;
+            }
+            this.p = None;
+            this.fix();
+            return Some(this);
+        };
+
+        // put the successor in place of `this`
+        let mut this = maybe_this.take()?;
+        next.p = this.p;
+        next.c = [this.c[0].take(), this.c[1].take()];
+        this.p = None;
+        this.fix();
+        let next = maybe_this.insert(next);
+        next.rebalance();
+        Some(this)
     }
 }
 
-impl<K: Ord, V> Node<K, V> {
+impl<K: Ord, V, A: Monoid<K, V>> Node<K, V, A> {
+    /// Splits subtree into keys `< key`, the node with `key` and keys `> key`
+    fn split3(
+        mut this: Link<Self>,
+        key: &K,
+    ) -> (Ref<Self>, Ref<Self>, Ref<Self>) {
+        let [l, r] = [this.c[0].take(), this.c[1].take()];
+        this.p = None;
+        match key.cmp(&this.kv.0) {
+            Ordering::Less => {
+                let (l0, m, l1) = match l {
+                    Some(l) => Self::split3(l, key),
+                    None => (None, None, None),
+                };
+                (l0, m, Some(Self::join(l1, this, r)))
+            }
+            Ordering::Greater => {
+                let (r0, m, r1) = match r {
+                    Some(r) => Self::split3(r, key),
+                    None => (None, None, None),
+                };
+                (Some(Self::join(l, this, r0)), m, r1)
+            }
+            Ordering::Equal => {
+                this.fix();
+                (l, Some(this), r)
+            }
+        }
+    }
+
+    /// Splits subtree into keys below and above `key`, the node with `key`
+    /// goes to the right part if `inclusive` is set
+    fn split(
+        this: Link<Self>,
+        key: &K,
+        inclusive: bool,
+    ) -> (Ref<Self>, Ref<Self>) {
+        match Self::split3(this, key) {
+            (l, None, r) => (l, r),
+            (l, Some(m), r) if inclusive => (l, Some(Self::join(None, m, r))),
+            (l, Some(m), r) => (Some(Self::join(l, m, None)), r),
+        }
+    }
+
     fn insert(
         maybe_this: &mut Ref<Self>,
-        p: *mut Self,
+        p: Option<NonNull<Self>>,
         kv: (K, V),
     ) -> Option<(K, V)> {
         let Some(this) = maybe_this else {
-            let this = Box::new(Node::root_p(kv, NonNull::new(p)));
-            *maybe_this = Some(this);
+            *maybe_this = Some(Link::new(Node::root_p(kv, p)));
             return None;
         };
 
-        let p = this.as_mut() as _;
-        match kv.0.cmp(&this.kv.0) {
+        let p = Some(this.ptr());
+        let old = match kv.0.cmp(&this.kv.0) {
             Ordering::Less => Self::insert(&mut this.c[0], p, kv),
             Ordering::Greater => Self::insert(&mut this.c[1], p, kv),
-            Ordering::Equal => Some(std::mem::replace(&mut this.kv, kv)),
+            Ordering::Equal => {
+                let old = std::mem::replace(&mut this.kv, kv);
+                this.fix();
+                return Some(old);
+            }
+        };
+        this.rebalance();
+        old
+    }
+
+    /// Number of keys in the subtree less than `key`
+    fn rank(&self, key: &K) -> usize {
+        let (mut cur, mut rank) = (Some(self), 0);
+        while let Some(node) = cur {
+            if *key <= node.kv.0 {
+                cur = node.c[0].as_deref();
+            } else {
+                rank += Self::size(&node.c[0]) + 1;
+                cur = node.c[1].as_deref();
+            }
         }
+        rank
     }
 
     fn find(&self, key: &K) -> Option<(&K, &V)> {
@@ -92,182 +417,504 @@ impl<K: Ord, V> Node<K, V> {
     }
 
     fn remove(maybe_this: &mut Ref<Self>, key: &K) -> Option<(K, V)> {
-        let this = maybe_this.as_deref_mut()?;
-        match key.cmp(&this.kv.0) {
+        let this = maybe_this.as_mut()?;
+        let removed = match key.cmp(&this.kv.0) {
             Ordering::Less => Self::remove(&mut this.c[0], key),
-            Ordering::Equal => Self::remove(&mut this.c[1], key),
-            Ordering::Greater => {
-                let lev = this.c[0].take();
-                let prav = this.c[1].take();
-                match (lev, prav) {
-                    (None, mut child) | (mut child, None) => {
-                        if let Some(child) = &mut child {
-                            child.p = this.p;
-                        }
-                        std::mem::replace(maybe_this, child).map(Node::box_pair)
-                    }
-                    (Some(mut lev), Some(mut prav)) => {
-                        let mut prav_lev_p = prav.as_mut() as *mut _;
-                        let mut prav_lev = &mut prav.c[0];
-                        while let Some(prav) = prav_lev {
-                            prav_lev_p = prav.as_mut() as *mut _;
-                            prav_lev = &mut prav.c[0];
-                        }
-                        lev.p = NonNull::new(prav_lev_p);
-                        *prav_lev = Some(lev);
-                        maybe_this.replace(prav).map(Node::box_pair)
-                    }
-                }
+            Ordering::Greater => Self::remove(&mut this.c[1], key),
+            Ordering::Equal => {
+                return Self::remove_this(maybe_this).map(|this| this.pair())
             }
-        }
+        };
+        this.rebalance();
+        removed
     }
 
-    fn split(mut self: Box<Self>, key: &K) -> (Ref<Self>, Ref<Self>) {
-        match key.cmp(&self.kv.0) {
-            Ordering::Less => match self.c[0].take() {
-                Some(l) => {
-                    let (mut l0, l1) = l.split(key);
-                    if let Some(l) = &mut l0 {
-                        l.p = None;
-                    }
-                    self.set_child(true, l1);
-                    (l0, Some(self))
-                }
-                None => (None, Some(self)),
-            },
-            Ordering::Greater => match self.c[1].take() {
-                Some(r) => {
-                    let (r0, mut r1) = r.split(key);
-                    if let Some(r) = &mut r1 {
-                        r.p = None;
-                    }
-                    self.set_child(false, r0);
-                    (Some(self), r1)
-                }
-                None => (Some(self), None),
-            },
-            Ordering::Equal => {
-                let mut r = self.c[1].take();
-                if let Some(r) = &mut r {
-                    r.p = None;
-                }
-                (Some(self), r)
+    /// Summary of the subtree entries within `start` and `end` bounds
+    ///
+    /// Once one of the bounds is passed, the other side of the descent is
+    /// unbounded and takes whole subtrees, so both sides take `O(log n)`.
+    fn fold(this: &Ref<Self>, start: Bound<&K>, end: Bound<&K>) -> A {
+        let Some(this) = this else {
+            return A::empty();
+        };
+        if let (Bound::Unbounded, Bound::Unbounded) = (start, end) {
+            return this.a.clone();
+        }
+        let (key, value) = this.pair_ref();
+        let above_start = match start {
+            Bound::Included(start) => key >= start,
+            Bound::Excluded(start) => key > start,
+            Bound::Unbounded => true,
+        };
+        let below_end = match end {
+            Bound::Included(end) => key <= end,
+            Bound::Excluded(end) => key < end,
+            Bound::Unbounded => true,
+        };
+        match (above_start, below_end) {
+            (false, _) => Self::fold(&this.c[1], start, end),
+            (_, false) => Self::fold(&this.c[0], start, end),
+            (true, true) => {
+                let l = Self::fold(&this.c[0], start, Bound::Unbounded);
+                let r = Self::fold(&this.c[1], Bound::Unbounded, end);
+                l.combine(&A::of(key, value)).combine(&r)
             }
         }
     }
 
-    fn set_child(&mut self, is_left: bool, mut c: Ref<Self>) {
-        if let Some(c) = &mut c {
-            c.p = Some(NonNull::from(&*self));
-        }
-        if is_left {
-            self.c[0] = c;
-        } else {
-            self.c[1] = c;
+    /// Keys of both subtrees, taking values from `a` for the common ones
+    fn union(a: Ref<Self>, b: Ref<Self>) -> Ref<Self> {
+        let Some(mut a) = a else {
+            return b;
+        };
+        let Some(b) = b else {
+            return Some(a);
+        };
+        let [al, ar] = [a.c[0].take(), a.c[1].take()];
+        let (bl, _, br) = Self::split3(b, &a.kv.0);
+        let (l, r) = (Self::union(al, bl), Self::union(ar, br));
+        Some(Self::join(l, a, r))
+    }
+
+    /// Entries of `a` with keys, that are present in `b`
+    fn intersection(a: Ref<Self>, b: Ref<Self>) -> Ref<Self> {
+        let (mut a, b) = (a?, b?);
+        let [al, ar] = [a.c[0].take(), a.c[1].take()];
+        let (bl, m, br) = Self::split3(b, &a.kv.0);
+        let l = Self::intersection(al, bl);
+        let r = Self::intersection(ar, br);
+        match m {
+            Some(_) => Some(Self::join(l, a, r)),
+            None => Self::join2(l, r),
         }
     }
 
-    #[allow(dead_code)]
-    fn rotate_left(self: &mut Box<Self>) {
-        let Some(b) = self.c[1].take() else {
-            return;
+    /// Entries of `a` with keys, that are missing from `b`
+    fn difference(a: Ref<Self>, b: Ref<Self>) -> Ref<Self> {
+        let a = a?;
+        let Some(mut b) = b else {
+            return Some(a);
         };
-        let mut a = std::mem::replace(self, b);
-        self.p = a.p;
-        let c = self.c[0].take();
-        a.set_child(false, c);
-        self.set_child(true, Some(a));
+        let [bl, br] = [b.c[0].take(), b.c[1].take()];
+        let (al, _, ar) = Self::split3(a, &b.kv.0);
+        let l = Self::difference(al, bl);
+        let r = Self::difference(ar, br);
+        Self::join2(l, r)
     }
+}
 
-    #[allow(dead_code)]
-    fn rotate_right(self: &mut Box<Self>) {
-        let Some(b) = self.c[0].take() else {
-            return;
-        };
-        let mut a = std::mem::replace(self, b);
-        self.p = a.p;
-        let c = self.c[1].take();
-        a.set_child(true, c);
-        self.set_child(false, Some(a));
+pub struct BinTree<K, V, A = ()> {
+    root: Ref<Node<K, V, A>>,
+}
+
+impl<K: Clone, V: Clone, A: Clone> Clone for BinTree<K, V, A> {
+    fn clone(&self) -> Self {
+        Self {
+            root: self.root.as_deref().map(Node::clone_subtree),
+        }
     }
 }
 
-#[derive(Clone, Hash, PartialEq, Eq)]
-pub struct BinTree<K, V> {
-    root: Ref<Node<K, V>>,
+impl<K: PartialEq, V: PartialEq, A> PartialEq for BinTree<K, V, A> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter())
+    }
 }
 
-impl<K, V> Default for BinTree<K, V> {
+impl<K: Eq, V: Eq, A> Eq for BinTree<K, V, A> {}
+
+impl<K: Hash, V: Hash, A> Hash for BinTree<K, V, A> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(self.len());
+        for entry in self.iter() {
+            entry.hash(state);
+        }
+    }
+}
+
+impl<K, V, A> Default for BinTree<K, V, A> {
     #[inline]
     fn default() -> Self {
         Self { root: None }
     }
 }
 
-impl<K: Debug + Ord, V: Debug> Debug for BinTree<K, V> {
+impl<K: Debug, V: Debug, A> Debug for BinTree<K, V, A> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_map().entries(self.iter()).finish()
-        // self.root.fmt(f)
     }
 }
 
 impl<K, V> BinTree<K, V> {
     #[inline]
-    fn new() -> Self {
+    pub fn new() -> Self {
         Self::default()
     }
 }
 
-impl<K: Ord, V> BinTree<K, V> {
+impl<K, V, A> BinTree<K, V, A> {
+    #[inline]
+    pub fn len(&self) -> usize {
+        Node::size(&self.root)
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    #[inline]
+    fn root_ptr(&self) -> Option<NonNull<Node<K, V, A>>> {
+        self.root.as_ref().map(Link::ptr)
+    }
+
+    pub fn iter(&self) -> iter::Iter<'_, K, V, A> {
+        iter::Iter::new(self.root_ptr())
+    }
+
+    pub fn keys(&self) -> iter::Keys<'_, K, V, A> {
+        iter::Keys::new(self.root_ptr())
+    }
+
+    pub fn values(&self) -> iter::Values<'_, K, V, A> {
+        iter::Values::new(self.root_ptr())
+    }
+
+    /// Cursor at the first entry, or at the ghost position if the tree is
+    /// empty
+    pub fn cursor_first(&self) -> cursor::Cursor<'_, K, V, A> {
+        let first = self.root_ptr().map(|root| unsafe { iter::first(root) });
+        cursor::Cursor::new(self, first)
+    }
+
+    pub fn cursor_last(&self) -> cursor::Cursor<'_, K, V, A> {
+        let last = self.root_ptr().map(|root| unsafe { iter::last(root) });
+        cursor::Cursor::new(self, last)
+    }
+
+    pub fn cursor_first_mut(&mut self) -> cursor::CursorMut<'_, K, V, A> {
+        let first = self.root_ptr().map(|root| unsafe { iter::first(root) });
+        cursor::CursorMut::new(self, first)
+    }
+
+    pub fn cursor_last_mut(&mut self) -> cursor::CursorMut<'_, K, V, A> {
+        let last = self.root_ptr().map(|root| unsafe { iter::last(root) });
+        cursor::CursorMut::new(self, last)
+    }
+}
+
+impl<K, V, A: Monoid<K, V>> BinTree<K, V, A> {
+    /// Link, that owns `node`: a child of its parent or the root
+    ///
+    /// # Safety
+    /// `node` must belong to this tree.
+    unsafe fn slot(
+        &mut self,
+        node: NonNull<Node<K, V, A>>,
+    ) -> &mut Ref<Node<K, V, A>> {
+        match unsafe { node.as_ref() }.p {
+            Some(mut p) => {
+                let p = unsafe { p.as_mut() };
+                let is_left = p.c[0].as_ref().map(Link::ptr) == Some(node);
+                &mut p.c[if is_left { 0 } else { 1 }]
+            }
+            None => &mut self.root,
+        }
+    }
+
+    /// Rebalances `node` and all of its ancestors after a change below it
+    ///
+    /// # Safety
+    /// `node` must belong to this tree.
+    unsafe fn rebalance_up(
+        &mut self,
+        mut node: Option<NonNull<Node<K, V, A>>>,
+    ) {
+        while let Some(ptr) = node {
+            let slot = unsafe { self.slot(ptr) };
+            let Some(this) = slot else {
+                break;
+            };
+            this.rebalance();
+            node = this.p;
+        }
+    }
+
+    /// Links a new node as the left or the right child of `p`, which must
+    /// be missing, or as the root if `p` is `None`
+    ///
+    /// # Safety
+    /// `p` must belong to this tree.
+    unsafe fn link(
+        &mut self,
+        p: Option<NonNull<Node<K, V, A>>>,
+        is_left: bool,
+        kv: (K, V),
+    ) -> NonNull<Node<K, V, A>> {
+        let node = Link::new(Node::root_p(kv, p));
+        let ptr = node.ptr();
+        match p {
+            Some(p) => {
+                let slot = unsafe { self.slot(p) };
+                let p = slot.as_mut().expect("parent belongs to the tree");
+                debug_assert!(p.c[if is_left { 0 } else { 1 }].is_none());
+                p.set_child(is_left, Some(node));
+            }
+            None => self.root = Some(node),
+        }
+        unsafe { self.rebalance_up(p) };
+        ptr
+    }
+
+    /// Removes `node` from the tree
+    ///
+    /// # Safety
+    /// `node` must belong to this tree, it is freed.
+    unsafe fn unlink(&mut self, node: NonNull<Node<K, V, A>>) -> (K, V) {
+        let p = unsafe { node.as_ref() }.p;
+        let slot = unsafe { self.slot(node) };
+        let removed = Node::remove_this(slot).map(|this| this.pair());
+        unsafe { self.rebalance_up(p) };
+        removed.expect("node belongs to the tree")
+    }
+
+    /// Makes `root` a root of the tree, clearing its parent pointer
+    #[inline]
+    fn detached(mut root: Ref<Node<K, V, A>>) -> Ref<Node<K, V, A>> {
+        if let Some(root) = &mut root {
+            root.p = None;
+        }
+        root
+    }
+}
+
+impl<K: Ord, V, A: Monoid<K, V>> BinTree<K, V, A> {
     pub fn insert(&mut self, key: K, value: V) -> Option<(K, V)> {
-        Node::insert(&mut self.root, 0 as _, (key, value))
+        Node::insert(&mut self.root, None, (key, value))
     }
 
     pub fn find(&self, key: &K) -> Option<(&K, &V)> {
         self.root.as_deref()?.find(key)
     }
 
-    pub fn find_mut(&mut self, key: &K) -> Option<(&K, &mut V)> {
-        self.root.as_deref_mut()?.find_mut(key)
+    /// Number of keys less than `key`, in `O(log n)`
+    pub fn rank(&self, key: &K) -> usize {
+        self.root.as_deref().map_or(0, |root| root.rank(key))
+    }
+
+    /// Entry with `i` smaller keys, in `O(log n)`
+    pub fn select(&self, i: usize) -> Option<(&K, &V)> {
+        self.iter().nth(i)
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        self.remove_entry(key).map(|(_, v)| v)
     }
 
-    pub fn remove(&mut self, key: &K) -> Option<(K, V)> {
+    pub fn remove_entry(&mut self, key: &K) -> Option<(K, V)> {
         Node::remove(&mut self.root, key)
     }
 
-    pub fn iter(&self) -> iter::Iter<'_, K, V> {
-        iter::Iter::new(self.root.as_deref().map(NonNull::from))
+    pub fn pop_first(&mut self) -> Option<(K, V)> {
+        Node::remove_edge(&mut self.root, true).map(|first| first.pair())
     }
 
-    pub fn keys(&self) -> iter::Keys<'_, K, V> {
-        iter::Keys::new(self.root.as_deref().map(NonNull::from))
+    pub fn pop_last(&mut self) -> Option<(K, V)> {
+        Node::remove_edge(&mut self.root, false).map(|last| last.pair())
     }
 
-    pub fn values(&self) -> iter::Values<'_, K, V> {
-        iter::Values::new(self.root.as_deref().map(NonNull::from))
+    /// Keeps only entries, for which `f` returns `true`, visiting them in
+    /// order and rebuilding the tree in `O(n)`
+    pub fn retain<F: FnMut(&K, &mut V) -> bool>(&mut self, mut f: F) {
+        let mut nodes = Vec::new();
+        Node::flatten(self.root.take(), &mut nodes);
+        nodes.retain_mut(|node| {
+            let (k, v) = node.pair_mut();
+            f(k, v)
+        });
+        let len = nodes.len();
+        self.root = Node::build(&mut nodes.into_iter(), len);
     }
 
-    pub fn iter_mut(&mut self) -> iter::IterMut<'_, K, V> {
-        iter::IterMut::new(self.root.as_deref_mut().map(NonNull::from))
+    /// Double-ended iterator over entries with keys within `range`
+    ///
+    /// Takes `O(log n)` to find both ends, then `O(1)` amortized per entry.
+    pub fn range<R: RangeBounds<K>>(
+        &self,
+        range: R,
+    ) -> iter::Iter<'_, K, V, A> {
+        let root = self.root_ptr();
+        iter::Iter::range(root, (range.start_bound(), range.end_bound()))
     }
 
-    pub fn values_mut(&mut self) -> iter::ValuesMut<'_, K, V> {
-        iter::ValuesMut::new(self.root.as_deref_mut().map(NonNull::from))
+    /// Combined summary of the entries with keys within `range`, in
+    /// `O(log n)`
+    pub fn fold_range<R: RangeBounds<K>>(&self, range: R) -> A {
+        Node::fold(&self.root, range.start_bound(), range.end_bound())
+    }
+
+    /// Cursor at the first entry with a key not less than `key`, or at the
+    /// ghost position if there is none
+    pub fn cursor_at(&self, key: &K) -> cursor::Cursor<'_, K, V, A> {
+        let node =
+            unsafe { iter::lower(self.root_ptr(), Bound::Included(key)) };
+        cursor::Cursor::new(self, node)
+    }
+
+    pub fn cursor_at_mut(&mut self, key: &K) -> cursor::CursorMut<'_, K, V, A> {
+        let node =
+            unsafe { iter::lower(self.root_ptr(), Bound::Included(key)) };
+        cursor::CursorMut::new(self, node)
+    }
+
+    /// First entry with a key not less than `key`
+    pub fn lower_bound(&self, key: &K) -> Option<(&K, &V)> {
+        self.range((Bound::Included(key), Bound::Unbounded)).next()
     }
 
+    /// First entry with a key greater than `key`
+    pub fn upper_bound(&self, key: &K) -> Option<(&K, &V)> {
+        self.range((Bound::Excluded(key), Bound::Unbounded)).next()
+    }
+
+    pub fn first_key_value(&self) -> Option<(&K, &V)> {
+        self.iter().next()
+    }
+
+    pub fn last_key_value(&self) -> Option<(&K, &V)> {
+        self.iter().next_back()
+    }
+
+    /// Moves keys `> key` to a new tree
     pub fn split(&mut self, key: &K) -> Self {
+        self.split_off(key, false)
+    }
+
+    /// Moves keys `> key`, or `>= key` if `inclusive` is set, to a new tree
+    /// in `O(log n)`
+    pub fn split_off(&mut self, key: &K, inclusive: bool) -> Self {
         match self.root.take() {
             Some(root) => {
-                let (root0, root1) = root.split(key);
-                self.root = root0;
-                Self { root: root1 }
+                let (root0, root1) = Node::split(root, key, inclusive);
+                self.root = Self::detached(root0);
+                Self {
+                    root: Self::detached(root1),
+                }
             }
-            None => Self::new(),
+            None => Self::default(),
+        }
+    }
+
+    /// Appends `other`, all keys of which must be greater than the keys of
+    /// `self`, in `O(log n)`
+    ///
+    /// # Panics
+    /// If key ranges of the trees overlap.
+    pub fn join(&mut self, mut other: Self) {
+        if let (Some((last, _)), Some((first, _))) =
+            (self.last_key_value(), other.first_key_value())
+        {
+            assert!(last < first, "joined trees must not overlap");
+        }
+        let root = Node::join2(self.root.take(), other.root.take());
+        self.root = Self::detached(root);
+    }
+
+    /// Moves all entries of `other` to `self`, replacing values of the
+    /// equal keys
+    ///
+    /// Takes `O(log n)` if key ranges of the trees do not overlap, otherwise
+    /// it is a [`union`](Self::union).
+    pub fn append(&mut self, other: &mut Self) {
+        let other = std::mem::take(other);
+        let order = match (self.last_key_value(), other.first_key_value()) {
+            (Some((last, _)), Some((first, _))) if last < first => Some(true),
+            _ => match (other.last_key_value(), self.first_key_value()) {
+                (Some((last, _)), Some((first, _))) if last < first => {
+                    Some(false)
+                }
+                _ => None,
+            },
+        };
+        let root = match order {
+            Some(true) => Node::join2(self.root.take(), other.root),
+            Some(false) => Node::join2(other.root, self.root.take()),
+            None => Node::union(other.root, self.root.take()),
+        };
+        self.root = Self::detached(root);
+    }
+
+    /// Entries with keys from either tree, values of the common keys are
+    /// taken from `self`
+    ///
+    /// Both this and other set operations take `O(m log(n / m + 1))` for
+    /// trees of sizes `m <= n`.
+    pub fn union(mut self, mut other: Self) -> Self {
+        let root = Node::union(self.root.take(), other.root.take());
+        Self {
+            root: Self::detached(root),
+        }
+    }
+
+    /// Entries of `self` with keys, that are present in `other`
+    pub fn intersection(mut self, mut other: Self) -> Self {
+        let root = Node::intersection(self.root.take(), other.root.take());
+        Self {
+            root: Self::detached(root),
+        }
+    }
+
+    /// Entries of `self` with keys, that are missing from `other`
+    pub fn difference(mut self, mut other: Self) -> Self {
+        let root = Node::difference(self.root.take(), other.root.take());
+        Self {
+            root: Self::detached(root),
         }
     }
 }
 
-impl<K: Ord, V> Extend<(K, V)> for BinTree<K, V> {
+/// Mutable access to values is only given without aggregates, as changing
+/// a value would leave the summaries of its ancestors stale
+impl<K: Ord, V> BinTree<K, V> {
+    pub fn find_mut(&mut self, key: &K) -> Option<(&K, &mut V)> {
+        self.root.as_deref_mut()?.find_mut(key)
+    }
+
+    /// Lazily removes and yields entries, for which `pred` returns `true`,
+    /// in order
+    ///
+    /// Entries, that are not visited before the iterator is dropped, are
+    /// kept.
+    pub fn extract_if<F: FnMut(&K, &mut V) -> bool>(
+        &mut self,
+        pred: F,
+    ) -> cursor::ExtractIf<'_, K, V, F> {
+        cursor::ExtractIf::new(self.cursor_first_mut(), pred)
+    }
+
+    /// Finds the place of `key` in one traversal for in-place manipulation
+    pub fn entry(&mut self, key: K) -> entry::Entry<'_, K, V> {
+        entry::Entry::new(self, key)
+    }
+
+    pub fn iter_mut(&mut self) -> iter::IterMut<'_, K, V> {
+        iter::IterMut::new(self.root_ptr())
+    }
+
+    pub fn values_mut(&mut self) -> iter::ValuesMut<'_, K, V> {
+        iter::ValuesMut::new(self.root_ptr())
+    }
+
+    pub fn range_mut<R: RangeBounds<K>>(
+        &mut self,
+        range: R,
+    ) -> iter::IterMut<'_, K, V> {
+        let root = self.root_ptr();
+        iter::IterMut::range(root, (range.start_bound(), range.end_bound()))
+    }
+}
+
+impl<K: Ord, V, A: Monoid<K, V>> Extend<(K, V)> for BinTree<K, V, A> {
     fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
         for (key, value) in iter {
             self.insert(key, value);
@@ -275,7 +922,9 @@ impl<K: Ord, V> Extend<(K, V)> for BinTree<K, V> {
     }
 }
 
-impl<K: Ord, V: Default> Extend<(K, Option<V>)> for BinTree<K, V> {
+impl<K: Ord, V: Default, A: Monoid<K, V>> Extend<(K, Option<V>)>
+    for BinTree<K, V, A>
+{
     fn extend<I: IntoIterator<Item = (K, Option<V>)>>(&mut self, iter: I) {
         for (key, value) in iter {
             self.insert(key, value.unwrap_or_default());
@@ -283,7 +932,7 @@ impl<K: Ord, V: Default> Extend<(K, Option<V>)> for BinTree<K, V> {
     }
 }
 
-impl<K: Ord, V: Default> Extend<K> for BinTree<K, V> {
+impl<K: Ord, V: Default, A: Monoid<K, V>> Extend<K> for BinTree<K, V, A> {
     fn extend<I: IntoIterator<Item = K>>(&mut self, iter: I) {
         for key in iter {
             self.insert(key, V::default());
@@ -291,18 +940,28 @@ impl<K: Ord, V: Default> Extend<K> for BinTree<K, V> {
     }
 }
 
-impl<K: Ord, V> FromIterator<(K, V)> for BinTree<K, V> {
+impl<K: Ord, V, A: Monoid<K, V>> FromIterator<(K, V)> for BinTree<K, V, A> {
     fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
-        let mut new = Self::new();
+        let mut new = Self::default();
         new.extend(iter);
         new
     }
 }
 
-impl<'n, K: Ord, V> IntoIterator for &'n BinTree<K, V> {
+impl<K, V, A: Monoid<K, V>> IntoIterator for BinTree<K, V, A> {
+    type Item = (K, V);
+
+    type IntoIter = iter::IntoIter<K, V, A>;
+
+    fn into_iter(mut self) -> Self::IntoIter {
+        iter::IntoIter::new(self.root.take())
+    }
+}
+
+impl<'n, K: Ord, V, A: Monoid<K, V>> IntoIterator for &'n BinTree<K, V, A> {
     type Item = (&'n K, &'n V);
 
-    type IntoIter = iter::Iter<'n, K, V>;
+    type IntoIter = iter::Iter<'n, K, V, A>;
 
     fn into_iter(self) -> Self::IntoIter {
         self.iter()
@@ -319,16 +978,965 @@ impl<'n, K: Ord, V> IntoIterator for &'n mut BinTree<K, V> {
     }
 }
 
+/// Serialized as a map in the order of keys, like `BTreeMap`
+#[cfg(feature = "serde")]
+impl<K: Serialize, V: Serialize, A> Serialize for BinTree<K, V, A> {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.iter())
+    }
+}
+
+/// Any map is accepted, later entries replace the earlier ones with the
+/// same key
+#[cfg(feature = "serde")]
+impl<'de, K, V, A> Deserialize<'de> for BinTree<K, V, A>
+where
+    K: Deserialize<'de> + Ord,
+    V: Deserialize<'de>,
+    A: Monoid<K, V>,
+{
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        use std::marker::PhantomData;
+
+        struct TreeVisitor<K, V, A>(PhantomData<BinTree<K, V, A>>);
+
+        impl<'de, K, V, A> Visitor<'de> for TreeVisitor<K, V, A>
+        where
+            K: Deserialize<'de> + Ord,
+            V: Deserialize<'de>,
+            A: Monoid<K, V>,
+        {
+            type Value = BinTree<K, V, A>;
+
+            fn expecting(
+                &self,
+                f: &mut std::fmt::Formatter,
+            ) -> std::fmt::Result {
+                f.write_str("a map")
+            }
+
+            fn visit_map<M: MapAccess<'de>>(
+                self,
+                mut map: M,
+            ) -> Result<Self::Value, M::Error> {
+                let mut tree = BinTree::default();
+                while let Some((key, value)) = map.next_entry()? {
+                    tree.insert(key, value);
+                }
+                Ok(tree)
+            }
+        }
+
+        deserializer.deserialize_map(TreeVisitor(PhantomData))
+    }
+}
+
 fn main() {
     let mut tree = BinTree::<_, usize>::new();
     tree.extend([8, 10, 14]);
-    // println!("{:?}", tree.remove(&3));
+    println!("{:?}", tree.remove(&10));
     println!("{:#?}", tree);
     println!("{:?}", tree.find(&5));
+    *tree.entry(5).or_default() += 1;
+    tree.entry(8).and_modify(|v| *v += 2);
+    println!("{:?}", tree);
+
+    println!("{:?}", tree.range(9..).collect::<Vec<_>>());
+    println!("{} {:?}", tree.len(), tree.select(1));
+
+    let mut it = tree.iter();
+    println!("{:?}", it.next());
+    println!("{:?}", it.next_back());
+    println!("{:?}", it.collect::<Vec<_>>());
+
+    println!("{}", tree.to_dot());
+}
+
+#[cfg(test)]
+mod bin_tree_test {
+    use std::{
+        collections::{BTreeMap, BTreeSet},
+        fmt::Debug,
+        ops::Bound,
+        ptr::NonNull,
+    };
+
+    use crate::{
+        entry::Entry, BinTree, IntervalTree, Monoid, Node, PersistentTree,
+    };
+
+    impl<K: Ord, V, A: Monoid<K, V> + PartialEq + Debug> Node<K, V, A> {
+        /// Checks ordering, heights, balance and parent links of the
+        /// subtree, returning its height
+        fn check(&self, lo: Option<&K>, hi: Option<&K>) -> u8 {
+            let key = &self.kv.0;
+            assert!(lo.is_none_or(|lo| lo < key), "keys are out of order");
+            assert!(hi.is_none_or(|hi| key < hi), "keys are out of order");
+
+            for c in self.c.iter().flatten() {
+                assert_eq!(c.p, Some(NonNull::from(self)), "stale parent");
+            }
+            let hl = self.c[0].as_ref().map_or(0, |l| l.check(lo, Some(key)));
+            let hr = self.c[1].as_ref().map_or(0, |r| r.check(Some(key), hi));
+            assert!(hl.abs_diff(hr) <= 1, "subtree is not balanced");
+            assert_eq!(self.h, 1 + hl.max(hr), "wrong height");
+            let n = 1 + Node::size(&self.c[0]) + Node::size(&self.c[1]);
+            assert_eq!(self.n, n, "wrong size");
+            let mut a = A::of(key, &self.kv.1);
+            if let Some(l) = &self.c[0] {
+                a = l.a.combine(&a);
+            }
+            if let Some(r) = &self.c[1] {
+                a = a.combine(&r.a);
+            }
+            assert_eq!(self.a, a, "wrong aggregate");
+            self.h
+        }
+    }
+
+    impl<K: Ord, V, A: Monoid<K, V> + PartialEq + Debug> BinTree<K, V, A> {
+        /// Panics if any of the tree invariants is broken
+        pub(crate) fn check(&self) {
+            if let Some(root) = &self.root {
+                assert_eq!(root.p, None, "root has a parent");
+                root.check(None, None);
+            }
+        }
+
+        pub(crate) fn height(&self) -> u8 {
+            Node::height(&self.root)
+        }
+    }
+
+    /// Deterministic xorshift sequence for randomized tests
+    pub(crate) fn random(mut seed: u64) -> impl Iterator<Item = u64> {
+        std::iter::from_fn(move || {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            Some(seed)
+        })
+    }
+
+    /// Upper bound of AVL tree height with `len` nodes
+    fn max_height(len: usize) -> u8 {
+        (1.45 * ((len + 2) as f64).log2()) as u8
+    }
+
+    #[test]
+    fn sorted_insert() {
+        let mut tree = BinTree::<_, ()>::new();
+        for i in 0..1000 {
+            tree.insert(i, ());
+            tree.check();
+        }
+        assert!(tree.height() <= max_height(1000));
+
+        let mut tree = BinTree::<_, ()>::new();
+        tree.extend((0..1000).rev());
+        tree.check();
+        assert!(tree.height() <= max_height(1000));
+    }
+
+    #[test]
+    fn random_insert_remove() {
+        let mut tree = BinTree::new();
+        let mut keys = BTreeSet::new();
+        for x in random(0x5eed).take(2000) {
+            let key = x % 512;
+            if x % 3 == 0 {
+                assert_eq!(
+                    tree.remove_entry(&key).map(|(k, _)| k),
+                    keys.take(&key)
+                );
+            } else {
+                tree.insert(key, x);
+                keys.insert(key);
+            }
+            tree.check();
+        }
+        assert!(tree.height() <= max_height(keys.len()));
+        for key in keys {
+            assert!(tree.remove(&key).is_some());
+            tree.check();
+        }
+        assert!(tree.root.is_none());
+    }
+
+    /// Runs random sequences of operations both on `BinTree` and `BTreeMap`
+    #[test]
+    fn same_as_btree_map() {
+        for seed in 1..=20 {
+            let mut tree = BinTree::new();
+            let mut map = BTreeMap::new();
+            let mut rng = random(seed);
+            for _ in 0..500 {
+                let x = rng.next().unwrap();
+                let key = x % 64;
+                match x / 64 % 8 {
+                    0 | 1 => assert_eq!(
+                        tree.insert(key, x).map(|(_, v)| v),
+                        map.insert(key, x)
+                    ),
+                    2 => assert_eq!(tree.remove(&key), map.remove(&key)),
+                    3 => assert_eq!(
+                        tree.remove_entry(&key),
+                        map.remove_entry(&key)
+                    ),
+                    4 => assert_eq!(tree.pop_first(), map.pop_first()),
+                    5 => assert_eq!(tree.pop_last(), map.pop_last()),
+                    6 if x.is_multiple_of(7) => {
+                        let keep = |k: &u64, v: &mut u64| {
+                            *v += 1;
+                            !(k ^ x).is_multiple_of(3)
+                        };
+                        tree.retain(keep);
+                        map.retain(keep);
+                    }
+                    _ => assert_eq!(tree.find(&key), map.get_key_value(&key)),
+                }
+                tree.check();
+
+                assert!(tree.iter().eq(map.iter()));
+                assert!(tree.iter().rev().eq(map.iter().rev()));
+                let (mut it, mut expected) = (tree.iter(), map.iter());
+                for i in 0.. {
+                    let (next, expected) = match (x >> (i % 64)) & 1 {
+                        0 => (it.next(), expected.next()),
+                        _ => (it.next_back(), expected.next_back()),
+                    };
+                    assert_eq!(next, expected);
+                    if next.is_none() {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn range() {
+        let mut rng = random(0xb0b);
+        let mut tree = BinTree::new();
+        let mut map = BTreeMap::new();
+        for x in rng.by_ref().take(300) {
+            tree.insert(x % 1000, x);
+            map.insert(x % 1000, x);
+        }
+        let bound = |x: u64| match x % 3 {
+            0 => Bound::Included(x / 3 % 1010),
+            1 => Bound::Excluded(x / 3 % 1010),
+            _ => Bound::Unbounded,
+        };
+        for _ in 0..500 {
+            let (x, y) = (rng.next().unwrap(), rng.next().unwrap());
+            let range = (bound(x), bound(y));
+            if let (
+                Bound::Included(s) | Bound::Excluded(s),
+                Bound::Included(e) | Bound::Excluded(e),
+            ) = range
+            {
+                // `BTreeMap::range` panics on reversed bounds and on
+                // `(Excluded(x), Excluded(x))`, while `BinTree::range` is
+                // just empty
+                let both_excluded =
+                    matches!(range, (Bound::Excluded(_), Bound::Excluded(_)));
+                if s > e || s == e && both_excluded {
+                    assert_eq!(tree.range(range).next(), None);
+                    continue;
+                }
+            }
+            assert!(tree.range(range).eq(map.range(range)));
+            assert!(tree.range(range).rev().eq(map.range(range).rev()));
+            let mut it = tree.range(range);
+            let mut expected = map.range(range);
+            loop {
+                let next = (it.next(), it.next_back());
+                assert_eq!(next, (expected.next(), expected.next_back()));
+                if next.0.is_none() {
+                    break;
+                }
+            }
+
+            let key = x % 1010;
+            assert_eq!(tree.lower_bound(&key), map.range(key..).next());
+            assert_eq!(
+                tree.upper_bound(&key),
+                map.range((Bound::Excluded(key), Bound::Unbounded)).next()
+            );
+        }
+        assert_eq!(tree.first_key_value(), map.first_key_value());
+        assert_eq!(tree.last_key_value(), map.last_key_value());
+
+        for (_, v) in tree.range_mut(100..200) {
+            *v = 0;
+        }
+        tree.check();
+        for (k, v) in tree.iter() {
+            assert_eq!(*v == 0, (100..200).contains(k));
+        }
+    }
+
+    #[test]
+    fn entry() {
+        let mut tree = BinTree::new();
+        let mut map = BTreeMap::new();
+        for x in random(0xe17).take(3000) {
+            let key = x % 256;
+            match x / 256 % 4 {
+                0 => {
+                    *tree.entry(key).or_default() += x % 1000;
+                    *map.entry(key).or_default() += x % 1000;
+                }
+                1 => {
+                    tree.entry(key).and_modify(|v| *v /= 2).or_insert(x);
+                    map.entry(key).and_modify(|v| *v /= 2).or_insert(x);
+                }
+                2 => {
+                    let removed = match tree.entry(key) {
+                        Entry::Occupied(entry) => Some(entry.remove_entry()),
+                        Entry::Vacant(entry) => {
+                            assert_eq!(entry.into_key(), key);
+                            None
+                        }
+                    };
+                    assert_eq!(removed, map.remove_entry(&key));
+                }
+                _ => {
+                    let value = tree.entry(key).or_insert_with_key(|k| k * 2);
+                    assert_eq!(value, map.entry(key).or_insert(key * 2));
+                }
+            }
+            tree.check();
+        }
+        assert!(tree.iter().eq(map.iter()));
+    }
+
+    fn random_tree(seed: u64, len: usize, modulo: u64) -> BinTree<u64, u64> {
+        random(seed).take(len).map(|x| (x % modulo, x)).collect()
+    }
+
+    #[test]
+    fn split_off_join() {
+        for (len, at) in [(0, 5), (1, 0), (300, 0), (300, 400), (300, 999)] {
+            for inclusive in [false, true] {
+                let mut tree = random_tree(len as u64 + 1, len, 1000);
+                let expected =
+                    tree.iter().map(|(k, v)| (*k, *v)).collect::<Vec<_>>();
+                let right = tree.split_off(&at, inclusive);
+                tree.check();
+                right.check();
+                assert!(tree.keys().all(|k| *k < at || *k == at && !inclusive));
+                assert!(right.keys().all(|k| *k > at || *k == at && inclusive));
+
+                tree.join(right);
+                tree.check();
+                assert!(tree.iter().map(|(k, v)| (*k, *v)).eq(expected));
+            }
+        }
+
+        // joining trees of very different heights
+        let mut small = BinTree::new();
+        small.insert(0, 0);
+        let mut large = random_tree(7, 1000, 1 << 20);
+        large.insert(1, 1);
+        let _ = large.split_off(&0, true).split_off(&1, false);
+        small.join(large);
+        small.check();
+    }
+
+    #[test]
+    #[should_panic]
+    fn join_overlapping() {
+        let mut tree = random_tree(1, 10, 100);
+        tree.join(random_tree(2, 10, 100));
+    }
+
+    #[test]
+    fn append() {
+        for (a, b) in
+            [(0..100, 100..150), (100..150, 0..100), (0..100, 50..150)]
+        {
+            let mut tree = a.clone().map(|k| (k, 0)).collect::<BinTree<_, _>>();
+            let mut other =
+                b.clone().map(|k| (k, 1)).collect::<BinTree<_, _>>();
+            let mut map = a.map(|k| (k, 0)).collect::<BTreeMap<_, _>>();
+            map.append(&mut b.map(|k| (k, 1)).collect());
+            tree.append(&mut other);
+            tree.check();
+            assert!(other.root.is_none());
+            assert!(tree.iter().eq(map.iter()));
+        }
+    }
+
+    #[test]
+    fn set_operations() {
+        for (seed, len) in [(1, 0), (2, 10), (3, 300), (4, 1000)] {
+            let a = || random_tree(seed, 500, 1000);
+            let b = || random_tree(seed + 100, len, 1000);
+            let to_map = |tree: BinTree<u64, u64>| {
+                tree.iter()
+                    .map(|(k, v)| (*k, *v))
+                    .collect::<BTreeMap<_, _>>()
+            };
+            let (ma, mb) = (to_map(a()), to_map(b()));
+
+            let union = a().union(b());
+            union.check();
+            let mut expected = mb.clone();
+            expected.extend(ma.clone());
+            assert!(union.iter().eq(expected.iter()));
+
+            let intersection = a().intersection(b());
+            intersection.check();
+            let mut expected = ma.clone();
+            expected.retain(|k, _| mb.contains_key(k));
+            assert!(intersection.iter().eq(expected.iter()));
+
+            let difference = a().difference(b());
+            difference.check();
+            let mut expected = ma;
+            expected.retain(|k, _| !mb.contains_key(k));
+            assert!(difference.iter().eq(expected.iter()));
+        }
+    }
+
+    #[test]
+    fn order_statistics() {
+        let mut tree = random_tree(0x1ead, 1000, 5000);
+        tree.retain(|k, _| k % 7 != 3);
+        tree.check();
+        let keys = tree.keys().copied().collect::<Vec<_>>();
+        assert_eq!(tree.len(), keys.len());
+        assert_eq!(tree.iter().len(), keys.len());
+
+        for key in (0..5000).step_by(13) {
+            assert_eq!(tree.rank(&key), keys.partition_point(|k| *k < key));
+        }
+        for (i, key) in keys.iter().enumerate() {
+            assert_eq!(tree.select(i).map(|(k, _)| k), Some(key));
+        }
+        assert_eq!(tree.select(keys.len()), None);
+
+        let mut it = tree.iter();
+        let mut expected = keys.iter();
+        for n in [0, 3, 1, 100, 0, 250] {
+            assert_eq!(it.nth(n).map(|(k, _)| k), expected.nth(n));
+            assert_eq!(it.nth_back(n).map(|(k, _)| k), expected.nth_back(n));
+            assert_eq!(it.len(), expected.len());
+        }
+        assert_eq!(it.nth(keys.len()), None);
+        assert_eq!(it.len(), 0);
+
+        let range = tree.range(1000..3000);
+        let expected = keys.iter().filter(|k| (1000..3000).contains(*k));
+        assert_eq!(range.len(), expected.clone().count());
+        assert!(range
+            .skip(10)
+            .step_by(7)
+            .map(|(k, _)| k)
+            .eq(expected.skip(10).step_by(7)));
+    }
+
+    /// Count and sum of values, the first and the last key of a range
+    #[derive(Clone, Debug, PartialEq)]
+    struct Stats {
+        count: usize,
+        sum: u64,
+        span: Option<(u64, u64)>,
+    }
+
+    impl Monoid<u64, u64> for Stats {
+        fn empty() -> Self {
+            Stats {
+                count: 0,
+                sum: 0,
+                span: None,
+            }
+        }
+
+        fn of(key: &u64, value: &u64) -> Self {
+            Stats {
+                count: 1,
+                sum: *value,
+                span: Some((*key, *key)),
+            }
+        }
+
+        fn combine(&self, other: &Self) -> Self {
+            Stats {
+                count: self.count + other.count,
+                sum: self.sum + other.sum,
+                span: match (self.span, other.span) {
+                    (Some((first, _)), Some((_, last))) => Some((first, last)),
+                    (span, None) | (None, span) => span,
+                },
+            }
+        }
+    }
+
+    #[test]
+    fn fold_range() {
+        let mut rng = random(0xf01d);
+        let mut tree = BinTree::<u64, u64, Stats>::default();
+        let mut map = BTreeMap::new();
+        for _ in 0..2000 {
+            let x = rng.next().unwrap();
+            let key = x % 500;
+            match x / 500 % 8 {
+                0 => assert_eq!(tree.remove(&key), map.remove(&key)),
+                1 => assert_eq!(tree.pop_first(), map.pop_first()),
+                2 if x.is_multiple_of(11) => {
+                    tree.retain(|k, v| {
+                        *v += 1;
+                        k % 5 != 0
+                    });
+                    map.retain(|k, v| {
+                        *v += 1;
+                        k % 5 != 0
+                    });
+                }
+                3 if x.is_multiple_of(13) => {
+                    let mut right = tree.split_off(&key, true);
+                    right.check();
+                    tree.append(&mut right);
+                }
+                _ => {
+                    let value = x % 1000;
+                    tree.insert(key, value);
+                    map.insert(key, value);
+                }
+            }
+            tree.check();
+
+            let (start, end) = (x % 512, (x >> 9) % 512);
+            let expected = map
+                .range(start.min(end)..start.max(end))
+                .fold(Stats::empty(), |acc, (k, v)| {
+                    acc.combine(&Stats::of(k, v))
+                });
+            assert_eq!(
+                tree.fold_range(start.min(end)..start.max(end)),
+                expected
+            );
+        }
+        assert_eq!(tree.fold_range(..).count, map.len());
+        assert_eq!(tree.fold_range(..).sum, map.values().sum::<u64>());
+    }
+
+    #[test]
+    fn into_iter() {
+        let tree = random_tree(0x1e7, 500, 1000);
+        let expected = tree.iter().map(|(k, v)| (*k, *v)).collect::<Vec<_>>();
+        let mut it = tree.into_iter();
+        assert_eq!(it.len(), expected.len());
+        assert_eq!(it.next_back(), expected.last().copied());
+        assert!(it.eq(expected[..expected.len() - 1].iter().copied()));
+    }
+
+    #[test]
+    fn extract_if() {
+        let mut tree = random_tree(0xe47, 500, 1000);
+        let mut map = tree
+            .iter()
+            .map(|(k, v)| (*k, *v))
+            .collect::<BTreeMap<_, _>>();
+
+        let odd = tree.extract_if(|k, _| k % 2 == 1).collect::<Vec<_>>();
+        tree.check();
+        assert!(odd.iter().all(|(k, v)| map.remove(k) == Some(*v)));
+        assert!(tree.iter().eq(map.iter()));
+
+        // stopping early keeps the rest
+        let first = tree
+            .extract_if(|k, v| {
+                *v = 0;
+                k % 3 == 0
+            })
+            .next()
+            .unwrap();
+        tree.check();
+        map.remove(&first.0);
+        assert_eq!(tree.len(), map.len());
+        assert_eq!(tree.find(&first.0), None);
+    }
+
+    #[test]
+    fn cursor() {
+        let mut tree = (0..100).map(|k| (k * 10, k)).collect::<BinTree<_, _>>();
+        let mut map = tree
+            .iter()
+            .map(|(k, v)| (*k, *v))
+            .collect::<BTreeMap<_, _>>();
+
+        let mut cursor = tree.cursor_at(&95);
+        assert_eq!(cursor.current(), Some((&100, &10)));
+        assert_eq!(cursor.peek_prev(), Some((&90, &9)));
+        cursor.move_prev();
+        cursor.move_prev();
+        assert_eq!(cursor.current(), Some((&80, &8)));
+        assert_eq!(tree.cursor_at(&1000).current(), None);
+        let mut cursor = tree.cursor_last();
+        cursor.move_next();
+        assert_eq!(cursor.current(), None);
+        assert_eq!(cursor.peek_next(), Some((&0, &0)));
+        assert_eq!(cursor.peek_prev(), Some((&990, &99)));
+
+        // walk forward, filling gaps and removing every third entry
+        let mut cursor = tree.cursor_first_mut();
+        let mut i = 0;
+        while let Some((&key, _)) = cursor.current() {
+            match i % 3 {
+                0 => {
+                    assert_eq!(cursor.remove_current(), map.remove_entry(&key))
+                }
+                1 => {
+                    cursor.insert_after(key + 5, i);
+                    map.insert(key + 5, i);
+                    cursor.move_next();
+                }
+                _ => {
+                    cursor.insert_before(key - 1, i);
+                    map.insert(key - 1, i);
+                    *cursor.current_mut().unwrap().1 += 1;
+                    *map.get_mut(&key).unwrap() += 1;
+                    cursor.move_next();
+                }
+            }
+            i += 1;
+        }
+        cursor.insert_after(-1, 0);
+        cursor.insert_before(10_000, 0);
+        map.extend([(-1, 0), (10_000, 0)]);
+        tree.check();
+        assert!(tree.iter().eq(map.iter()));
+
+        let mut empty = BinTree::new();
+        empty.cursor_first_mut().insert_before(1, 1);
+        assert_eq!(empty.first_key_value(), Some((&1, &1)));
+    }
+
+    #[test]
+    #[should_panic = "key is out of order"]
+    fn cursor_insert_out_of_order() {
+        let mut tree = (0..10).map(|k| (k * 10, ())).collect::<BinTree<_, _>>();
+        tree.cursor_at_mut(&50).insert_after(65, ());
+    }
+
+    #[test]
+    fn split() {
+        for at in [-1, 0, 17, 250, 499, 500] {
+            let mut tree = BinTree::<i32, ()>::new();
+            tree.extend(0..500);
+            let right = tree.split(&at);
+            tree.check();
+            right.check();
+            assert!(tree.height() <= max_height(500));
+            assert!(right.height() <= max_height(500));
+            for key in 0..500 {
+                let (left, right) = (tree.find(&key), right.find(&key));
+                assert_eq!(left.is_some(), key <= at);
+                assert_eq!(right.is_some(), key > at);
+            }
+        }
+    }
 
-    // let mut it = tree.iter();
-    // println!("{:?}", it.next());
-    // println!("{:?}", it.next());
-    // println!("{:?}", it.next_back());
-    // println!("{:?}", it.collect::<Vec<_>>());
+    #[test]
+    fn persistent() {
+        let mut versions = vec![(PersistentTree::new(), BTreeMap::new())];
+        for (i, x) in random(11).take(2000).enumerate() {
+            let (tree, map) = &versions[(x % versions.len() as u64) as usize];
+            let (mut tree, mut map) = (tree.clone(), map.clone());
+            let key = x % 300;
+            if x % 3 == 0 {
+                let next = tree.remove(&key);
+                assert_eq!(next.ptr_eq(&tree), map.remove(&key).is_none());
+                tree = next;
+            } else {
+                let next = tree.insert(key, i);
+                // only the path to the new node is copied, and a rotation
+                // may build one node more
+                let copied = next.unshared(&tree);
+                assert!(copied <= max_height(next.len()) as usize + 1);
+                map.insert(key, i);
+                tree = next;
+            }
+            tree.check();
+            assert_eq!(tree.len(), map.len());
+            assert!(tree.iter().eq(map.iter()));
+            assert_eq!(tree.get(&key), map.get(&key));
+            versions.push((tree, map));
+        }
+        // older versions are left unchanged
+        for (tree, map) in &versions {
+            assert!(tree.iter().eq(map.iter()));
+            assert_eq!(tree.first_key_value(), map.first_key_value());
+            assert_eq!(tree.last_key_value(), map.last_key_value());
+        }
+        let (tree, _) = versions.pop().unwrap();
+        assert_eq!(tree.clone(), tree.iter().map(|(k, v)| (*k, *v)).collect());
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serde() {
+        let tree = random_tree(5, 300, 1000);
+        let map = tree.iter().collect::<BTreeMap<_, _>>();
+        let json = serde_json::to_string(&tree).unwrap();
+        assert_eq!(json, serde_json::to_string(&map).unwrap());
+        let back = serde_json::from_str::<BinTree<u64, u64>>(&json).unwrap();
+        back.check();
+        assert_eq!(back, tree);
+
+        let json = r#"{"3": 1, "1": 2, "3": 3}"#;
+        let tree = serde_json::from_str::<BinTree<u64, u64>>(json).unwrap();
+        assert!(tree.into_iter().eq([(1, 2), (3, 3)]));
+        assert!(serde_json::from_str::<BinTree<u64, u64>>("[1]").is_err());
+    }
+
+    #[test]
+    fn to_dot() {
+        assert_eq!(BinTree::<u8, u8>::new().to_dot().lines().count(), 3);
+
+        let tree = (0..7)
+            .map(|k| (k, format!("<{}|{}>", k, k)))
+            .collect::<BinTree<_, _>>();
+        let dot = tree.to_dot();
+        assert!(dot.starts_with("digraph {"));
+        assert_eq!(dot.matches("[label=\"").count(), 7);
+        assert_eq!(dot.matches("[label=L]").count(), 3);
+        assert_eq!(dot.matches("[label=R]").count(), 3);
+        assert_eq!(dot.matches("color=gray").count(), 6);
+        assert!(!dot.contains("color=red"));
+        assert!(dot.contains(r#"{3 | \"\<3\|3\>\"} | h=3 n=7 bf=0"#));
+    }
+
+    #[test]
+    fn interval_tree() {
+        let mut tree = IntervalTree::new();
+        let mut intervals = BTreeMap::new();
+        let mut rng = random(17);
+        for i in 0..1000 {
+            let (x, y) = (rng.next().unwrap(), rng.next().unwrap());
+            let (start, len) = (x % 1000, 1 + y % 50);
+            if x % 4 == 0 {
+                let range = intervals
+                    .range((start, 0)..)
+                    .next()
+                    .map(|(&(s, e), _)| s..e);
+                if let Some(range) = range {
+                    let old = intervals.remove(&(range.start, range.end));
+                    assert_eq!(tree.remove(&range), old);
+                }
+            } else {
+                let old = intervals.insert((start, start + len), i);
+                assert_eq!(tree.insert(start..start + len, i), old);
+            }
+            tree.check();
+            assert_eq!(tree.len(), intervals.len());
+
+            let expected = |lo: u64, hi: u64| {
+                intervals
+                    .iter()
+                    .filter(|(&(s, e), _)| lo < hi && s < hi && lo < e)
+                    .map(|(&(s, e), v)| (s, e, *v))
+                    .collect::<Vec<_>>()
+            };
+            let found = |it: crate::interval::Overlapping<u64, usize>| {
+                it.map(|(r, v)| (*r.start, *r.end, *v)).collect::<Vec<_>>()
+            };
+            let (lo, hi) = (y % 1000, y % 1000 + x % 30);
+            assert_eq!(found(tree.overlapping(lo..hi)), expected(lo, hi));
+            assert_eq!(tree.overlaps(lo..hi), !expected(lo, hi).is_empty());
+            assert_eq!(found(tree.containing(lo)), expected(lo, lo + 1));
+        }
+        assert!(tree
+            .iter()
+            .map(|(r, v)| ((*r.start, *r.end), v))
+            .eq(intervals.iter().map(|(k, v)| (*k, v))));
+        assert_eq!(tree.get(&(0..0)), None);
+    }
+
+    #[test]
+    fn interval_tree_empty_range() {
+        let mut tree = IntervalTree::new();
+        tree.insert(0..10, 'a');
+        tree.insert(4..6, 'b');
+        tree.insert(5..7, 'c');
+        assert_eq!(tree.containing(5).count(), 3);
+        assert_eq!(tree.overlapping(5..6).count(), 3);
+        for &(start, end) in &[(5, 5), (6, 5), (0, 0), (10, 10)] {
+            assert_eq!(tree.overlapping(start..end).next(), None);
+            assert!(!tree.overlaps(start..end));
+        }
+    }
+}
+
+/// Cases, that exercise the raw pointers, meant to be run under Miri as well:
+/// `cargo +nightly miri test soundness`
+#[cfg(test)]
+mod soundness_test {
+    use std::{rc::Rc, thread};
+
+    use crate::{bin_tree_test::random, cursor, entry, iter, BinTree, Monoid};
+
+    const LEN: usize = if cfg!(miri) { 40 } else { 400 };
+
+    fn tree(len: usize) -> BinTree<u64, u64> {
+        (0..len as u64).map(|k| (k, k)).collect()
+    }
+
+    #[test]
+    fn iter_mut_references_stay_valid() {
+        let mut tree = tree(LEN);
+        let mut it = tree.iter_mut();
+        let mut values = Vec::new();
+        // the walk from both ends reads nodes, values of which are already
+        // borrowed
+        while let Some((_, v)) = it.next() {
+            values.push(v);
+            values.extend(it.next_back().map(|(_, v)| v));
+        }
+        for v in values {
+            *v += 1;
+        }
+        let mut values = tree.values_mut().collect::<Vec<_>>();
+        values.reverse();
+        for v in values {
+            *v *= 2;
+        }
+        for (_, v) in tree.range_mut(..LEN as u64 / 2).rev() {
+            *v += 1;
+        }
+        let expected = (0..LEN as u64)
+            .map(|k| (k, (k + 1) * 2 + (k < LEN as u64 / 2) as u64));
+        assert!(tree.into_iter().eq(expected));
+    }
+
+    #[test]
+    fn entries_and_cursors_across_rotations() {
+        let mut tree = BinTree::new();
+        for k in random(3).take(LEN).map(|x| x % 64) {
+            *tree.entry(k).or_insert(0) += 1;
+            if k % 3 == 0 {
+                if let entry::Entry::Occupied(entry) = tree.entry(k / 3) {
+                    entry.remove();
+                }
+            }
+            tree.check();
+        }
+
+        let mut tree = BinTree::new();
+        let mut cursor = tree.cursor_first_mut();
+        for k in 0..LEN as u64 {
+            // sorted inserts rotate the nodes under the cursor
+            cursor.insert_before(k, k);
+        }
+        cursor.move_next();
+        while let Some((k, _)) = cursor.current() {
+            match k % 2 {
+                0 => drop(cursor.remove_current()),
+                _ => cursor.move_next(),
+            }
+        }
+        tree.check();
+        assert!(tree.keys().copied().eq((1..LEN as u64).step_by(2)));
+    }
+
+    #[test]
+    fn clone_owns_its_links() {
+        let mut tree = tree(LEN);
+        let copy = tree.clone();
+        copy.check();
+        for k in 0..LEN as u64 / 2 {
+            tree.remove(&(k * 2));
+            tree.insert(k * 2 + LEN as u64, 0);
+        }
+        tree.check();
+        drop(tree);
+        copy.check();
+        assert!(copy
+            .iter()
+            .map(|(k, v)| (*k, *v))
+            .eq((0..LEN as u64).map(|k| (k, k))));
+        let mut cursor = copy.cursor_last();
+        cursor.move_prev();
+        assert_eq!(
+            cursor.current(),
+            Some((&(LEN as u64 - 2), &(LEN as u64 - 2)))
+        );
+    }
+
+    #[test]
+    fn drops_every_entry_once() {
+        let value = Rc::new(());
+        let fill = |len: usize| -> BinTree<u64, Rc<()>> {
+            (0..len as u64).map(|k| (k, value.clone())).collect()
+        };
+
+        let mut it = fill(LEN).into_iter();
+        it.next();
+        it.next_back();
+        drop(it);
+
+        let mut tree = fill(LEN);
+        tree.extract_if(|k, _| k % 3 == 0).take(5).for_each(drop);
+        tree.pop_first();
+        tree.pop_last();
+        tree.retain(|k, _| k % 5 != 0);
+        let mut right = tree.split_off(&(LEN as u64 / 2), true);
+        tree.append(&mut right);
+        tree.check();
+        drop(tree);
+
+        let (a, b) = (fill(LEN), fill(LEN / 2));
+        drop(a.difference(b));
+        assert_eq!(Rc::strong_count(&value), 1);
+    }
+
+    #[test]
+    fn send_sync() {
+        fn send_sync<T: Send + Sync>() {}
+        send_sync::<BinTree<u64, String>>();
+        send_sync::<iter::Iter<'_, u64, String>>();
+        send_sync::<iter::IterMut<'_, u64, String>>();
+        send_sync::<iter::IntoIter<u64, String>>();
+        send_sync::<cursor::Cursor<'_, u64, String>>();
+        send_sync::<cursor::CursorMut<'_, u64, String>>();
+        send_sync::<entry::Entry<'_, u64, String>>();
+
+        #[derive(Clone)]
+        struct Sum(u64);
+
+        impl Monoid<u64, u64> for Sum {
+            fn empty() -> Self {
+                Sum(0)
+            }
+
+            fn of(_: &u64, value: &u64) -> Self {
+                Sum(*value)
+            }
+
+            fn combine(&self, other: &Self) -> Self {
+                Sum(self.0 + other.0)
+            }
+        }
+
+        let tree: BinTree<u64, u64, Sum> =
+            (0..LEN as u64).map(|k| (k, k)).collect();
+        let sum = (0..LEN as u64).sum::<u64>();
+        thread::scope(|s| {
+            for _ in 0..2 {
+                s.spawn(|| assert_eq!(tree.values().sum::<u64>(), sum));
+            }
+        });
+        let mut tree = thread::spawn(move || tree).join().unwrap();
+        tree.insert(LEN as u64, 0);
+        assert_eq!(tree.fold_range(..).0, sum);
+    }
 }
@@ -0,0 +1,26 @@
+/// Summary of the entries of a subtree, e.g. a sum of values or a count
+///
+/// Every node keeps `combine` of its left subtree, its own entry and its
+/// right subtree, in that order. `combine` must be associative with `empty`
+/// as its identity for [`BinTree::fold_range`](crate::BinTree::fold_range)
+/// to be correct, but does not have to be commutative.
+pub trait Monoid<K, V>: Clone {
+    fn empty() -> Self;
+
+    /// Summary of a single entry
+    fn of(key: &K, value: &V) -> Self;
+
+    fn combine(&self, other: &Self) -> Self;
+}
+
+/// No summary, the default for trees, that do not need aggregates
+impl<K, V> Monoid<K, V> for () {
+    #[inline]
+    fn empty() -> Self {}
+
+    #[inline]
+    fn of(_: &K, _: &V) -> Self {}
+
+    #[inline]
+    fn combine(&self, _: &Self) -> Self {}
+}
@@ -0,0 +1,362 @@
+use std::{cmp::Ordering, fmt::Debug, iter::FromIterator, sync::Arc};
+
+/// Node of [`PersistentTree`], which is shared between versions and never
+/// changed after it is built
+struct PNode<K, V> {
+    kv: (K, V),
+    c: [PRef<K, V>; 2],
+    /// Height of the subtree, leaf has height 1
+    h: u8,
+    /// Number of nodes in the subtree
+    n: usize,
+}
+
+type PRef<K, V> = Option<Arc<PNode<K, V>>>;
+
+#[inline]
+fn height<K, V>(this: &PRef<K, V>) -> u8 {
+    this.as_ref().map_or(0, |this| this.h)
+}
+
+#[inline]
+fn size<K, V>(this: &PRef<K, V>) -> usize {
+    this.as_ref().map_or(0, |this| this.n)
+}
+
+impl<K, V> PNode<K, V> {
+    fn new(kv: (K, V), l: PRef<K, V>, r: PRef<K, V>) -> Arc<Self> {
+        Arc::new(Self {
+            h: 1 + height(&l).max(height(&r)),
+            n: 1 + size(&l) + size(&r),
+            kv,
+            c: [l, r],
+        })
+    }
+}
+
+impl<K: Clone, V: Clone> PNode<K, V> {
+    /// Builds a node over `l` and `r`, which may differ in height by two
+    /// after a single insertion or removal, rotating the new nodes
+    fn balance(kv: (K, V), l: PRef<K, V>, r: PRef<K, V>) -> Arc<Self> {
+        let (hl, hr) = (height(&l), height(&r));
+        if hl > hr + 1 {
+            let l = l.expect("left subtree is higher");
+            let [ll, lr] = &l.c;
+            if height(ll) >= height(lr) {
+                let r = Self::new(kv, lr.clone(), r);
+                return Self::new(l.kv.clone(), ll.clone(), Some(r));
+            }
+            let lr = lr.as_ref().expect("inner subtree is higher");
+            let l0 = Self::new(l.kv.clone(), ll.clone(), lr.c[0].clone());
+            let r = Self::new(kv, lr.c[1].clone(), r);
+            Self::new(lr.kv.clone(), Some(l0), Some(r))
+        } else if hr > hl + 1 {
+            let r = r.expect("right subtree is higher");
+            let [rl, rr] = &r.c;
+            if height(rr) >= height(rl) {
+                let l = Self::new(kv, l, rl.clone());
+                return Self::new(r.kv.clone(), Some(l), rr.clone());
+            }
+            let rl = rl.as_ref().expect("inner subtree is higher");
+            let l = Self::new(kv, l, rl.c[0].clone());
+            let r0 = Self::new(r.kv.clone(), rl.c[1].clone(), rr.clone());
+            Self::new(rl.kv.clone(), Some(l), Some(r0))
+        } else {
+            Self::new(kv, l, r)
+        }
+    }
+
+    /// Removes the leftmost node of the subtree, copying the path to it
+    fn remove_min(&self) -> ((K, V), PRef<K, V>) {
+        let [l, r] = &self.c;
+        match l {
+            None => (self.kv.clone(), r.clone()),
+            Some(l) => {
+                let (min, l) = l.remove_min();
+                (min, Some(Self::balance(self.kv.clone(), l, r.clone())))
+            }
+        }
+    }
+}
+
+impl<K: Ord + Clone, V: Clone> PNode<K, V> {
+    fn insert(this: &PRef<K, V>, kv: (K, V)) -> Arc<Self> {
+        let Some(this) = this else {
+            return Self::new(kv, None, None);
+        };
+        let [l, r] = &this.c;
+        match kv.0.cmp(&this.kv.0) {
+            Ordering::Less => {
+                let l = Self::insert(l, kv);
+                Self::balance(this.kv.clone(), Some(l), r.clone())
+            }
+            Ordering::Greater => {
+                let r = Self::insert(r, kv);
+                Self::balance(this.kv.clone(), l.clone(), Some(r))
+            }
+            Ordering::Equal => Self::new(kv, l.clone(), r.clone()),
+        }
+    }
+
+    /// New subtree without `key`, `None` if there is nothing to remove, so
+    /// the caller keeps sharing the old one
+    fn remove(&self, key: &K) -> Option<PRef<K, V>> {
+        let [l, r] = &self.c;
+        match key.cmp(&self.kv.0) {
+            Ordering::Less => {
+                let l = l.as_ref()?.remove(key)?;
+                Some(Some(Self::balance(self.kv.clone(), l, r.clone())))
+            }
+            Ordering::Greater => {
+                let r = r.as_ref()?.remove(key)?;
+                Some(Some(Self::balance(self.kv.clone(), l.clone(), r)))
+            }
+            Ordering::Equal => Some(match (l, r) {
+                (None, c) | (c, None) => c.clone(),
+                (Some(_), Some(r)) => {
+                    let (min, r) = r.remove_min();
+                    Some(Self::balance(min, l.clone(), r))
+                }
+            }),
+        }
+    }
+}
+
+/// Immutable version of [`BinTree`](crate::BinTree), that is cheap to clone
+///
+/// Updates return a new version, that copies only the path from the root to
+/// the changed node and shares all the other nodes with the old version, so
+/// an update takes `O(log n)` time and memory. Nodes are reference counted
+/// with `Arc`, so versions can be sent to and shared between threads.
+pub struct PersistentTree<K, V> {
+    root: PRef<K, V>,
+}
+
+impl<K, V> Clone for PersistentTree<K, V> {
+    /// Another handle to the same version, in `O(1)`
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            root: self.root.clone(),
+        }
+    }
+}
+
+impl<K, V> Default for PersistentTree<K, V> {
+    #[inline]
+    fn default() -> Self {
+        Self { root: None }
+    }
+}
+
+impl<K: Debug, V: Debug> Debug for PersistentTree<K, V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K: PartialEq, V: PartialEq> PartialEq for PersistentTree<K, V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.ptr_eq(other)
+            || self.len() == other.len() && self.iter().eq(other.iter())
+    }
+}
+
+impl<K: Eq, V: Eq> Eq for PersistentTree<K, V> {}
+
+impl<K, V> PersistentTree<K, V> {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        size(&self.root)
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    /// Whether both trees are the same version, without comparing entries
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        match (&self.root, &other.root) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            (a, b) => a.is_none() && b.is_none(),
+        }
+    }
+
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        let mut iter = Iter {
+            stack: Vec::with_capacity(height(&self.root) as usize),
+            len: self.len(),
+        };
+        iter.push_left(&self.root);
+        iter
+    }
+
+    pub fn first_key_value(&self) -> Option<(&K, &V)> {
+        let mut node = self.root.as_deref()?;
+        while let Some(l) = node.c[0].as_deref() {
+            node = l;
+        }
+        Some((&node.kv.0, &node.kv.1))
+    }
+
+    pub fn last_key_value(&self) -> Option<(&K, &V)> {
+        let mut node = self.root.as_deref()?;
+        while let Some(r) = node.c[1].as_deref() {
+            node = r;
+        }
+        Some((&node.kv.0, &node.kv.1))
+    }
+}
+
+impl<K: Ord, V> PersistentTree<K, V> {
+    pub fn find(&self, key: &K) -> Option<(&K, &V)> {
+        let mut cur = self.root.as_deref();
+        while let Some(node) = cur {
+            match key.cmp(&node.kv.0) {
+                Ordering::Less => cur = node.c[0].as_deref(),
+                Ordering::Greater => cur = node.c[1].as_deref(),
+                Ordering::Equal => return Some((&node.kv.0, &node.kv.1)),
+            }
+        }
+        None
+    }
+
+    #[inline]
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.find(key).map(|(_, v)| v)
+    }
+
+    #[inline]
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.find(key).is_some()
+    }
+}
+
+impl<K: Ord + Clone, V: Clone> PersistentTree<K, V> {
+    /// New version with `key` set to `value`, this one is left unchanged
+    #[must_use]
+    pub fn insert(&self, key: K, value: V) -> Self {
+        Self {
+            root: Some(PNode::insert(&self.root, (key, value))),
+        }
+    }
+
+    /// New version without `key`, which shares all of the nodes with this
+    /// one if `key` is missing
+    #[must_use]
+    pub fn remove(&self, key: &K) -> Self {
+        match self.root.as_ref().and_then(|root| root.remove(key)) {
+            Some(root) => Self { root },
+            None => self.clone(),
+        }
+    }
+}
+
+impl<K: Ord + Clone, V: Clone> Extend<(K, V)> for PersistentTree<K, V> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (key, value) in iter {
+            *self = self.insert(key, value);
+        }
+    }
+}
+
+impl<K: Ord + Clone, V: Clone> FromIterator<(K, V)> for PersistentTree<K, V> {
+    fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
+        let mut new = Self::default();
+        new.extend(iter);
+        new
+    }
+}
+
+impl<'t, K, V> IntoIterator for &'t PersistentTree<K, V> {
+    type Item = (&'t K, &'t V);
+    type IntoIter = Iter<'t, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// In-order iterator over [`PersistentTree`], that keeps the path to the
+/// next node on a stack, as nodes have no parent links
+pub struct Iter<'t, K, V> {
+    stack: Vec<&'t PNode<K, V>>,
+    len: usize,
+}
+
+impl<'t, K, V> Iter<'t, K, V> {
+    fn push_left(&mut self, mut cur: &'t PRef<K, V>) {
+        while let Some(node) = cur {
+            self.stack.push(node);
+            cur = &node.c[0];
+        }
+    }
+}
+
+impl<'t, K, V> Iterator for Iter<'t, K, V> {
+    type Item = (&'t K, &'t V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        self.len -= 1;
+        self.push_left(&node.c[1]);
+        Some((&node.kv.0, &node.kv.1))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'t, K, V> ExactSizeIterator for Iter<'t, K, V> {}
+
+impl<'t, K, V> std::iter::FusedIterator for Iter<'t, K, V> {}
+
+#[cfg(test)]
+impl<K: Ord, V> PersistentTree<K, V> {
+    /// Panics if any of the tree invariants is broken
+    pub(crate) fn check(&self) {
+        fn check<K: Ord, V>(
+            this: &PRef<K, V>,
+            lo: Option<&K>,
+            hi: Option<&K>,
+        ) -> u8 {
+            let Some(this) = this else {
+                return 0;
+            };
+            let key = &this.kv.0;
+            assert!(lo.is_none_or(|lo| lo < key), "keys are out of order");
+            assert!(hi.is_none_or(|hi| key < hi), "keys are out of order");
+            let hl = check(&this.c[0], lo, Some(key));
+            let hr = check(&this.c[1], Some(key), hi);
+            assert!(hl.abs_diff(hr) <= 1, "subtree is not balanced");
+            assert_eq!(this.h, 1 + hl.max(hr), "wrong height");
+            let n = 1 + size(&this.c[0]) + size(&this.c[1]);
+            assert_eq!(this.n, n, "wrong size");
+            this.h
+        }
+        check(&self.root, None, None);
+    }
+
+    /// Number of nodes, that are not shared with `other`
+    pub(crate) fn unshared(&self, other: &Self) -> usize {
+        fn nodes<K, V>(this: &PRef<K, V>, out: &mut Vec<*const PNode<K, V>>) {
+            if let Some(this) = this {
+                out.push(Arc::as_ptr(this));
+                nodes(&this.c[0], out);
+                nodes(&this.c[1], out);
+            }
+        }
+        let (mut a, mut b) = (Vec::new(), Vec::new());
+        nodes(&self.root, &mut a);
+        nodes(&other.root, &mut b);
+        let b = b.into_iter().collect::<std::collections::HashSet<_>>();
+        a.into_iter().filter(|node| !b.contains(node)).count()
+    }
+}
@@ -1,6 +1,8 @@
 use bincode::Options;
 use serde::{de::DeserializeOwned, Serialize};
 
+mod key;
+
 fn options() -> impl Options {
     bincode::options()
         .allow_trailing_bytes()
@@ -18,3 +20,230 @@ pub fn de<T: DeserializeOwned>(
 ) -> Result<T, bincode::Error> {
     options().deserialize(raw_t.as_ref())
 }
+
+/// Encodes a storage key, so that encoded keys compare in the same order as
+/// the keys, see [`key`]
+///
+/// Maps in keys must iterate in a fixed order, like `BTreeMap`.
+pub fn ser_key<T: Serialize>(t: &T) -> Vec<u8> {
+    let mut serializer = key::KeySerializer { out: Vec::new() };
+    t.serialize(&mut serializer)
+        .expect("Should always serialize");
+    serializer.out
+}
+
+/// Decodes a storage key, that takes the whole of `raw_t`
+pub fn de_key<T: DeserializeOwned>(
+    raw_t: impl AsRef<[u8]>,
+) -> Result<T, bincode::Error> {
+    let mut deserializer = key::KeyDeserializer {
+        input: raw_t.as_ref(),
+    };
+    let t = T::deserialize(&mut deserializer)?;
+    if !deserializer.input.is_empty() {
+        return Err(serde::de::Error::custom("trailing bytes after key"));
+    }
+    Ok(t)
+}
+
+#[cfg(test)]
+mod key_test {
+    use std::{collections::BTreeMap, fmt::Debug};
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use super::*;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord)]
+    enum Kind {
+        Unit,
+        Newtype(i32),
+        Tuple(u8, String),
+        Struct { name: String, tag: Option<u16> },
+    }
+
+    /// Bytes, that go through `serialize_bytes` rather than a sequence
+    #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+    struct Bytes(Vec<u8>);
+
+    impl Serialize for Bytes {
+        fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+            s.serialize_bytes(&self.0)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Bytes {
+        fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+            struct BytesVisitor;
+
+            impl serde::de::Visitor<'_> for BytesVisitor {
+                type Value = Bytes;
+
+                fn expecting(
+                    &self,
+                    f: &mut std::fmt::Formatter<'_>,
+                ) -> std::fmt::Result {
+                    f.write_str("bytes")
+                }
+
+                fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Bytes, E> {
+                    Ok(Bytes(v))
+                }
+            }
+
+            d.deserialize_byte_buf(BytesVisitor)
+        }
+    }
+
+    /// Pseudo-random numbers, so the pairs are the same on every run
+    fn numbers(n: usize) -> impl Iterator<Item = u64> {
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        (0..n).map(move |_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        })
+    }
+
+    /// Round-trips every value, checks that the encodings compare as the
+    /// values do, and that no encoding is a strict prefix of another
+    fn check<T>(values: &[T])
+    where
+        T: Serialize + DeserializeOwned + PartialOrd + Debug,
+    {
+        let raw = values.iter().map(ser_key).collect::<Vec<_>>();
+        for (value, raw_value) in values.iter().zip(&raw) {
+            assert_eq!(&de_key::<T>(raw_value).unwrap(), value);
+        }
+        for (a, raw_a) in values.iter().zip(&raw) {
+            for (b, raw_b) in values.iter().zip(&raw) {
+                assert_eq!(
+                    a.partial_cmp(b),
+                    Some(raw_a.cmp(raw_b)),
+                    "{:?} and {:?}",
+                    a,
+                    b
+                );
+                if raw_a != raw_b {
+                    assert!(!raw_b.starts_with(raw_a), "{:?} {:?}", a, b);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn integers() {
+        let mut unsigned = vec![0, 1, 0xFF, 0x100, u64::MAX - 1, u64::MAX];
+        unsigned.extend(numbers(100));
+        check(&unsigned);
+        check(&unsigned.iter().map(|&n| n as u8).collect::<Vec<_>>());
+        check(&[0, 1, u128::MAX / 2, u128::MAX]);
+
+        let mut signed = vec![i64::MIN, i64::MIN + 1, -256, -1, 0, 1, i64::MAX];
+        signed.extend(numbers(100).map(|n| n as i64));
+        check(&signed);
+        check(&signed.iter().map(|&n| n as i8).collect::<Vec<_>>());
+        check(&signed.iter().map(|&n| n as i32).collect::<Vec<_>>());
+        check(&[i128::MIN, -1, 0, 1, i128::MAX]);
+    }
+
+    #[test]
+    fn floats_chars_bools() {
+        check(&[
+            f64::NEG_INFINITY,
+            -1e300,
+            -2.5,
+            -0.5,
+            0.0,
+            1e-300,
+            2.5,
+            1e300,
+        ]);
+        check(&[f32::MIN, -1.0, 0.0, f32::MIN_POSITIVE, f32::MAX]);
+        check(&['\0', 'a', 'z', '\u{ff}', '\u{10ffff}']);
+        check(&[false, true]);
+    }
+
+    #[test]
+    fn strings_and_bytes() {
+        let strings = [
+            "", "\0", "\0\0", "\0\u{1}", "\0a", "\u{1}", "a", "a\0", "a\0\0",
+            "a\u{1}", "aa", "b", "\u{ff}", "\u{ff}\0",
+        ];
+        check(&strings.map(String::from));
+
+        let bytes = [
+            vec![],
+            vec![0x00],
+            vec![0x00, 0x00],
+            vec![0x00, 0xFF],
+            vec![0x00, 0xFF, 0x00],
+            vec![0x01],
+            vec![0xFF],
+            vec![0xFF, 0x00],
+            vec![0xFF, 0xFF],
+        ];
+        check(&bytes.clone().map(Bytes));
+        check(&bytes);
+        assert_eq!(ser_key(&Bytes(vec![0x00, 0xFF])), [0, 0xFF, 0xFF, 0, 0]);
+    }
+
+    #[test]
+    fn compound() {
+        check(&[None, Some(0u8), Some(1), Some(u8::MAX)]);
+        check(&[None, Some(String::new()), Some("\0".to_string())]);
+        check(&[
+            (-1i32, String::from("b"), 0u8),
+            (0, String::new(), 9),
+            (0, String::from("\0"), 0),
+            (0, String::from("a"), 0),
+            (0, String::from("a"), 1),
+            (1, String::new(), 0),
+        ]);
+        check(&[
+            vec![],
+            vec![0u16],
+            vec![0, 0],
+            vec![0, 1],
+            vec![1],
+            vec![u16::MAX],
+        ]);
+        check(&[vec![String::new()], vec![String::new(), String::new()]]);
+        check(&[
+            Kind::Unit,
+            Kind::Newtype(i32::MIN),
+            Kind::Newtype(0),
+            Kind::Tuple(0, String::from("a")),
+            Kind::Tuple(0, String::from("a\0")),
+            Kind::Tuple(1, String::new()),
+            Kind::Struct {
+                name: String::new(),
+                tag: None,
+            },
+            Kind::Struct {
+                name: String::new(),
+                tag: Some(0),
+            },
+            Kind::Struct {
+                name: String::from("x"),
+                tag: None,
+            },
+        ]);
+        let maps = [vec![], vec![(1u8, 2u8)], vec![(1, 3)], vec![(2, 0)]];
+        check(&maps.map(|map| map.into_iter().collect::<BTreeMap<_, _>>()));
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(de_key::<u64>([0; 7]).is_err());
+        assert!(de_key::<u64>([0; 9]).is_err());
+        assert!(de_key::<String>(b"a\0").is_err());
+        assert!(de_key::<String>(b"a\0\x01").is_err());
+        assert!(de_key::<String>([0xC3, 0x28, 0, 0]).is_err());
+        assert!(de_key::<Option<u8>>([2, 0]).is_err());
+        assert!(de_key::<char>(0xD800u32.to_be_bytes()).is_err());
+        assert!(de_key::<Kind>([0, 0, 0, 9]).is_err());
+        assert_eq!(de_key::<(u8, u8)>([1, 2]).unwrap(), (1, 2));
+    }
+}
@@ -0,0 +1,59 @@
+use std::fmt::{self, Display, Formatter};
+
+/// Error of the storage operations
+#[derive(Debug)]
+pub enum StorageError {
+    /// RocksDB failed to read or write
+    Io(rocksdb::Error),
+    /// Stored bytes do not decode into the expected type, as in a corrupted
+    /// or outdated value
+    Decode(bincode::Error),
+    /// There is no value under the key, which is the raw key in the database
+    Missing(Vec<u8>),
+}
+
+impl StorageError {
+    /// Whether the key is not found, rather than the storage failed
+    #[inline]
+    pub fn is_missing(&self) -> bool {
+        matches!(self, StorageError::Missing(_))
+    }
+}
+
+impl Display for StorageError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageError::Io(err) => write!(f, "storage io error: {}", err),
+            StorageError::Decode(err) => {
+                write!(f, "cannot decode stored value: {}", err)
+            }
+            StorageError::Missing(key) => {
+                write!(f, "no value under key {:?}", key)
+            }
+        }
+    }
+}
+
+impl std::error::Error for StorageError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            StorageError::Io(err) => Some(err),
+            StorageError::Decode(err) => Some(err),
+            StorageError::Missing(_) => None,
+        }
+    }
+}
+
+impl From<rocksdb::Error> for StorageError {
+    #[inline]
+    fn from(err: rocksdb::Error) -> Self {
+        StorageError::Io(err)
+    }
+}
+
+impl From<bincode::Error> for StorageError {
+    #[inline]
+    fn from(err: bincode::Error) -> Self {
+        StorageError::Decode(err)
+    }
+}
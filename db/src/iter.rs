@@ -0,0 +1,364 @@
+use std::{
+    marker::PhantomData,
+    ops::{Bound, RangeBounds},
+};
+
+use rocksdb::{DBIterator, Direction, IteratorMode, ReadOptions};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{codec, error::StorageError};
+
+/// Smallest key after all of the keys starting with `prefix`, `None` if
+/// there is none, as the prefix is all `FF`
+pub(crate) fn prefix_end(prefix: &[u8]) -> Option<Vec<u8>> {
+    let mut end = prefix.to_vec();
+    while let Some(last) = end.pop() {
+        if last < u8::MAX {
+            end.push(last + 1);
+            return Some(end);
+        }
+    }
+    None
+}
+
+/// Raw key bounds `lower..upper` of the keys in `range` under `prefix`
+///
+/// Encoded keys are never a prefix of each other, so the keys after `key`
+/// start at `key ++ 00`.
+fn key_bounds<Q: Serialize>(
+    prefix: &[u8],
+    range: &impl RangeBounds<Q>,
+) -> (Vec<u8>, Option<Vec<u8>>) {
+    let raw_key = |key: &Q, after: bool| {
+        let mut raw_key = [prefix, &codec::ser_key(key)].concat();
+        if after {
+            raw_key.push(0);
+        }
+        raw_key
+    };
+    let lower = match range.start_bound() {
+        Bound::Included(key) => raw_key(key, false),
+        Bound::Excluded(key) => raw_key(key, true),
+        Bound::Unbounded => prefix.to_vec(),
+    };
+    let upper = match range.end_bound() {
+        Bound::Included(key) => Some(raw_key(key, true)),
+        Bound::Excluded(key) => Some(raw_key(key, false)),
+        Bound::Unbounded => prefix_end(prefix),
+    };
+    (lower, upper)
+}
+
+/// Decoded entry with its raw key
+type RawEntry<K, V> = (Box<[u8]>, K, V);
+
+/// Position after the last entry of a [`Page`], that the next page starts
+/// from
+///
+/// The token is the encoded key of the entry, so it can be handed out to a
+/// client as bytes and taken back with `From<Vec<u8>>`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PageToken(Vec<u8>);
+
+impl AsRef<[u8]> for PageToken {
+    #[inline]
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl From<Vec<u8>> for PageToken {
+    #[inline]
+    fn from(raw: Vec<u8>) -> Self {
+        Self(raw)
+    }
+}
+
+impl From<PageToken> for Vec<u8> {
+    #[inline]
+    fn from(token: PageToken) -> Self {
+        token.0
+    }
+}
+
+/// Entries of a storage in key order, see
+/// [`StorageMap::page`](crate::storage_map::StorageMap::page)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Page<K, V> {
+    pub items: Vec<(K, V)>,
+    /// Token of the next page in the same direction, `None` if this one is
+    /// the last
+    pub next: Option<PageToken>,
+}
+
+/// Iterator over the entries under a prefix, in the order of the keys or in
+/// reverse
+///
+/// RocksDB stops the iterator at the bounds of the prefix, so it never reads
+/// the keys of other storages. Entries, that fail to decode, are returned
+/// as errors rather than skipped.
+pub struct Iter<'a, K, V> {
+    inner: DBIterator<'a>,
+    /// Length of the prefix, that is cut from the keys before decoding
+    prefix_len: usize,
+    _phantom: PhantomData<(K, V)>,
+}
+
+impl<'a, K: DeserializeOwned, V: DeserializeOwned> Iter<'a, K, V> {
+    fn with_bounds(
+        rocks_db: &'a rocksdb::DB,
+        prefix_len: usize,
+        (lower, upper): (Vec<u8>, Option<Vec<u8>>),
+        direction: Direction,
+    ) -> Self {
+        let mut read_options = ReadOptions::default();
+        read_options.set_iterate_lower_bound(lower);
+        if let Some(upper) = upper {
+            read_options.set_iterate_upper_bound(upper);
+        }
+        let mode = match direction {
+            Direction::Forward => IteratorMode::Start,
+            Direction::Reverse => IteratorMode::End,
+        };
+        Self {
+            inner: rocks_db.iterator_opt(mode, read_options),
+            prefix_len,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Entries under `prefix` with the keys in `range`
+    pub(crate) fn new<Q: Serialize>(
+        rocks_db: &'a rocksdb::DB,
+        prefix: &[u8],
+        range: &impl RangeBounds<Q>,
+        direction: Direction,
+    ) -> Self {
+        let bounds = key_bounds(prefix, range);
+        Self::with_bounds(rocks_db, prefix.len(), bounds, direction)
+    }
+
+    /// Entries under `prefix` past the `token` of the previous page in
+    /// `direction`, or from the first one without a token
+    pub(crate) fn from_token(
+        rocks_db: &'a rocksdb::DB,
+        prefix: &[u8],
+        token: Option<&PageToken>,
+        direction: Direction,
+    ) -> Self {
+        let bounds = match (token, direction) {
+            (None, _) => (prefix.to_vec(), prefix_end(prefix)),
+            (Some(token), Direction::Forward) => {
+                let lower = [prefix, token.as_ref(), &[0]].concat();
+                (lower, prefix_end(prefix))
+            }
+            (Some(token), Direction::Reverse) => {
+                let upper = [prefix, token.as_ref()].concat();
+                (prefix.to_vec(), Some(upper))
+            }
+        };
+        Self::with_bounds(rocks_db, prefix.len(), bounds, direction)
+    }
+
+    fn next_raw(&mut self) -> Option<Result<RawEntry<K, V>, StorageError>> {
+        let (raw_key, raw_value) = match self.inner.next()? {
+            Ok(raw) => raw,
+            Err(err) => return Some(Err(err.into())),
+        };
+        let decoded = codec::de_key::<K>(&raw_key[self.prefix_len..])
+            .and_then(|key| Ok((key, codec::de::<V>(raw_value)?)));
+        Some(match decoded {
+            Ok((key, value)) => Ok((raw_key, key, value)),
+            Err(err) => Err(err.into()),
+        })
+    }
+
+    /// Takes up to `limit` entries, with the token of the following ones
+    ///
+    /// # Panics
+    /// If `limit` is zero.
+    pub(crate) fn page(
+        mut self,
+        limit: usize,
+    ) -> Result<Page<K, V>, StorageError> {
+        assert!(limit > 0, "page limit is zero");
+        let mut items = Vec::new();
+        let mut last_key = None;
+        while items.len() < limit {
+            let Some(next) = self.next_raw() else {
+                return Ok(Page { items, next: None });
+            };
+            let (raw_key, key, value) = next?;
+            items.push((key, value));
+            last_key = Some(raw_key);
+        }
+        // no token after the last entry, so the last page is not empty
+        let next = match self.inner.next() {
+            Some(_) => last_key
+                .map(|raw_key| PageToken(raw_key[self.prefix_len..].to_vec())),
+            None => None,
+        };
+        Ok(Page { items, next })
+    }
+}
+
+impl<'a, K: DeserializeOwned, V: DeserializeOwned> Iterator for Iter<'a, K, V> {
+    type Item = Result<(K, V), StorageError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.next_raw()?.map(|(_, key, value)| (key, value)))
+    }
+}
+
+#[cfg(test)]
+mod iter_test {
+    use std::ops::Bound::{self, *};
+
+    use super::*;
+    use crate::{
+        storage_double_map, storage_map, test_db::TempDb, StorageInfo,
+    };
+
+    struct NumbersInfo;
+    impl StorageInfo for NumbersInfo {
+        const STORAGE_NAME: &'static str = "Numbers";
+    }
+
+    struct PairsInfo;
+    impl StorageInfo for PairsInfo {
+        const STORAGE_NAME: &'static str = "Pairs";
+    }
+
+    type Numbers = storage_map::StorageMap<i32, String, NumbersInfo>;
+    type Pairs = storage_double_map::StorageMap<String, u16, i32, PairsInfo>;
+
+    fn fill(db: &TempDb) -> (Numbers, Pairs) {
+        let numbers = Numbers::new(db);
+        for key in -10..10 {
+            numbers.put(&key, &key.to_string()).unwrap();
+        }
+        let pairs = Pairs::new(db);
+        for (key_0, key_1) in
+            [("a", 2), ("a", 1), ("a\0", 0), ("", 5), ("b", 0)]
+        {
+            pairs.put((&key_0.to_string(), &key_1), &-1).unwrap();
+        }
+        (numbers, pairs)
+    }
+
+    fn keys<K: DeserializeOwned, V: DeserializeOwned>(
+        iter: Iter<'_, K, V>,
+    ) -> Vec<K> {
+        iter.map(|entry| entry.unwrap().0).collect()
+    }
+
+    /// Keys of all pages, checking that only the last page has no token
+    fn pages<K, V>(
+        mut page: impl FnMut(Option<&PageToken>) -> Page<K, V>,
+    ) -> Vec<K> {
+        let mut keys = Vec::new();
+        let mut token = None;
+        loop {
+            let Page { items, next } = page(token.as_ref());
+            assert!(!items.is_empty(), "empty page");
+            keys.extend(items.into_iter().map(|(key, _)| key));
+            match next {
+                Some(next) => token = Some(next),
+                None => return keys,
+            }
+        }
+    }
+
+    #[test]
+    fn prefix_ends() {
+        assert_eq!(prefix_end(&[]), None);
+        assert_eq!(prefix_end(&[0xFF]), None);
+        assert_eq!(prefix_end(&[0xFF, 0xFF]), None);
+        assert_eq!(prefix_end(&[1, 2]), Some(vec![1, 3]));
+        assert_eq!(prefix_end(&[1, 0xFF]), Some(vec![2]));
+        assert_eq!(prefix_end(&[0, 0xFF, 0xFF]), Some(vec![1]));
+    }
+
+    #[test]
+    fn bounds() {
+        let bounds =
+            |range: (Bound<u8>, Bound<u8>)| key_bounds(&[7, 0xFF], &range);
+        assert_eq!(
+            bounds((Unbounded, Unbounded)),
+            (vec![7, 0xFF], Some(vec![8]))
+        );
+        assert_eq!(bounds((Included(3), Unbounded)).0, [7, 0xFF, 3]);
+        assert_eq!(bounds((Excluded(3), Unbounded)).0, [7, 0xFF, 3, 0]);
+        assert_eq!(
+            bounds((Unbounded, Included(3))).1,
+            Some(vec![7, 0xFF, 3, 0])
+        );
+        assert_eq!(bounds((Unbounded, Excluded(3))).1, Some(vec![7, 0xFF, 3]));
+        assert_eq!(
+            bounds((Excluded(0xFF), Included(0xFF))),
+            (vec![7, 0xFF, 0xFF, 0], Some(vec![7, 0xFF, 0xFF, 0]))
+        );
+        // the prefix has no successor, so the keys are not bounded above
+        assert_eq!(key_bounds::<u8>(&[0xFF], &..), (vec![0xFF], None));
+        assert_eq!(key_bounds::<u8>(&[], &..), (vec![], None));
+    }
+
+    #[test]
+    fn ranges() {
+        let db = TempDb::new("ranges");
+        let (numbers, pairs) = fill(&db);
+        assert_eq!(keys(numbers.iter()), (-10..10).collect::<Vec<_>>());
+        assert_eq!(
+            keys(numbers.iter_rev()),
+            (-10..10).rev().collect::<Vec<_>>()
+        );
+        assert_eq!(keys(numbers.range(-2..2)), [-2, -1, 0, 1]);
+        assert_eq!(keys(numbers.range_rev(..=-8)), [-8, -9, -10]);
+        assert_eq!(keys(numbers.iter_from(&8)), [8, 9]);
+        assert_eq!(keys(numbers.range((Excluded(-4), Included(-2)))), [-3, -2]);
+        assert_eq!(keys(numbers.range(20..)), []);
+
+        let all = keys(pairs.iter());
+        let expected = [("", 5), ("a", 1), ("a", 2), ("a\0", 0), ("b", 0)]
+            .map(|(key_0, key_1)| (key_0.to_string(), key_1));
+        assert_eq!(all, expected);
+        let mut reversed = keys(pairs.iter_rev());
+        reversed.reverse();
+        assert_eq!(reversed, expected);
+        assert_eq!(keys(pairs.iter_key(&"a".into())), [1, 2]);
+        assert_eq!(keys(pairs.iter_key_rev(&"a".into())), [2, 1]);
+        assert_eq!(keys(pairs.range_key(&"a".into(), 2..)), [2]);
+        assert_eq!(keys(pairs.iter_key(&"c".into())), []);
+    }
+
+    #[test]
+    fn paging() {
+        let db = TempDb::new("paging");
+        let (numbers, pairs) = fill(&db);
+        let forward = (-10..10).collect::<Vec<_>>();
+        let reverse = (-10..10).rev().collect::<Vec<_>>();
+        for limit in 1..=forward.len() + 1 {
+            assert_eq!(pages(|t| numbers.page(t, limit).unwrap()), forward);
+            assert_eq!(pages(|t| numbers.page_rev(t, limit).unwrap()), reverse);
+        }
+        for limit in 1..=3 {
+            let a = String::from("a");
+            assert_eq!(
+                pages(|t| pairs.page_key(&a, t, limit).unwrap()),
+                [1, 2]
+            );
+            assert_eq!(
+                pages(|t| pairs.page_key_rev(&a, t, limit).unwrap()),
+                [2, 1]
+            );
+        }
+
+        // the token is a position, so the next page starts right after it
+        // even if the last entry is gone
+        let first = numbers.page(None, 5).unwrap();
+        numbers.remove(&-6).unwrap();
+        let second = numbers.page(first.next.as_ref(), 5).unwrap();
+        let keys = second.items.iter().map(|(key, _)| *key).collect::<Vec<_>>();
+        assert_eq!(keys, [-5, -4, -3, -2, -1]);
+    }
+}
@@ -3,9 +3,12 @@ use std::sync::Arc;
 use rocksdb::{DBCompressionType, Options};
 
 pub mod codec;
+pub mod error;
+pub mod iter;
 pub mod message_format;
 pub mod storage_double_map;
 pub mod storage_map;
+pub mod transaction;
 
 use message_format::*;
 
@@ -30,12 +33,23 @@ pub type Messages = storage_map::StorageMap<
     MessagesInfo,
 >;
 
+pub struct MessagesByTimeInfo;
+impl StorageInfo for MessagesByTimeInfo {
+    const STORAGE_NAME: &'static str = "MessagesByTime";
+}
+
+/// Index of [`Messages`] by their timestamps
+pub type MessagesByTime =
+    storage_double_map::StorageMap<u64, MessageId, (), MessagesByTimeInfo>;
+
 fn main() -> anyhow::Result<()> {
     env_logger::Builder::from_default_env()
         .filter_level(log::LevelFilter::Info)
         .try_init()?;
 
-    let path = "./base";
+    // keys of a `./base` written before the order-preserving key encoding
+    // do not decode, so the demo uses a new directory instead of reading it
+    let path = "./base-v2";
     let mut open_options = Options::default();
     open_options.create_if_missing(true);
     open_options.enable_statistics();
@@ -44,6 +58,7 @@ fn main() -> anyhow::Result<()> {
 
     let db = Arc::new(rocksdb::DB::open(&open_options, path)?);
     let messages = Messages::new(&db);
+    let messages_by_time = MessagesByTime::new(&db);
 
     messages.put(
         &1,
@@ -53,7 +68,7 @@ fn main() -> anyhow::Result<()> {
             _phantom: Default::default(),
         }
         .into(),
-    );
+    )?;
     messages.put(
         &2,
         &MessageValueV1 {
@@ -62,15 +77,96 @@ fn main() -> anyhow::Result<()> {
             _phantom: Default::default(),
         }
         .into(),
-    );
+    )?;
 
-    messages.iter().for_each(|(key, value)| {
+    for entry in messages.iter() {
+        let (key, value) = entry?;
         println!("{:?} => {:?}", key, value);
-    });
+    }
 
-    messages.remove(&3);
+    messages.remove(&3)?;
 
-    println!("{:?}", messages.get(&2));
+    let mut tx = transaction::Transaction::new(&db);
+    messages.put_in(
+        &mut tx,
+        &4,
+        &MessageValueV1 {
+            msg: "ghi".into(),
+            timestamp: 4,
+            _phantom: Default::default(),
+        }
+        .into(),
+    );
+    messages_by_time.put_in(&mut tx, (&4, &4), &());
+    println!("{:?}", messages.get_in(&tx, &4)?);
+    tx.commit()?;
+
+    for entry in messages_by_time.iter() {
+        let ((timestamp, id), ()) = entry?;
+        println!("{:?} @ {:?}", id, timestamp);
+    }
+
+    let mut token = None;
+    loop {
+        let page = messages.page(token.as_ref(), 2)?;
+        println!("{:?}", page.items);
+        match page.next {
+            Some(next) => token = Some(next),
+            None => break,
+        }
+    }
+
+    println!("{:?}", messages.get(&2)?);
+    match messages.get(&3) {
+        Err(err) if err.is_missing() => println!("3 is removed"),
+        other => println!("{:?}", other?),
+    }
 
     Ok(())
 }
+
+#[cfg(test)]
+pub(crate) mod test_db {
+    use std::{ops::Deref, path::PathBuf, sync::Arc};
+
+    use rocksdb::{Options, DB};
+
+    /// Database in a fresh temporary directory, that is destroyed on drop
+    pub(crate) struct TempDb {
+        db: Option<Arc<DB>>,
+        path: PathBuf,
+    }
+
+    impl TempDb {
+        pub(crate) fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "db-{}-{}",
+                name,
+                std::process::id()
+            ));
+            let _ = DB::destroy(&Options::default(), &path);
+            let mut options = Options::default();
+            options.create_if_missing(true);
+            let db = DB::open(&options, &path).expect("temporary database");
+            Self {
+                db: Some(Arc::new(db)),
+                path,
+            }
+        }
+    }
+
+    impl Deref for TempDb {
+        type Target = Arc<DB>;
+
+        fn deref(&self) -> &Arc<DB> {
+            self.db.as_ref().expect("database is open until drop")
+        }
+    }
+
+    impl Drop for TempDb {
+        fn drop(&mut self) {
+            drop(self.db.take());
+            let _ = DB::destroy(&Options::default(), &self.path);
+        }
+    }
+}
@@ -1,9 +1,21 @@
-use std::{marker::PhantomData, sync::Arc};
+use std::{marker::PhantomData, ops::RangeBounds, sync::Arc};
 
+use rocksdb::Direction;
 use serde::{de::DeserializeOwned, Serialize};
 
-use crate::{codec, StorageInfo};
+use crate::{
+    codec,
+    error::StorageError,
+    iter::{Iter, Page, PageToken},
+    transaction::Transaction,
+    StorageInfo,
+};
 
+/// Typed storage with a pair of keys under the prefix of `INFO`, entries
+/// with the same first key are next to each other
+///
+/// As with [`crate::storage_map::StorageMap`], only the `*_in` methods see
+/// the writes staged in a [`Transaction`].
 pub struct StorageMap<K0, K1, V, INFO> {
     inner: Arc<rocksdb::DB>,
     _phantom: PhantomData<(K0, K1, V, INFO)>,
@@ -24,84 +36,254 @@ impl<
     }
 
     fn get_semi_key(key: &K0) -> Vec<u8> {
-        let raw_key = codec::ser(key);
+        let raw_key = codec::ser_key(key);
         [&INFO::prefix()[..], &raw_key].concat()
     }
 
     fn get_final_key(keys: (&K0, &K1)) -> Vec<u8> {
-        let raw_key_0 = codec::ser(keys.0);
-        let raw_key_1 = codec::ser(keys.1);
+        let raw_key_0 = codec::ser_key(keys.0);
+        let raw_key_1 = codec::ser_key(keys.1);
         [&INFO::prefix()[..], &raw_key_0, &raw_key_1].concat()
     }
 
-    pub fn put(&self, keys: (&K0, &K1), value: &V) {
+    pub fn put(&self, keys: (&K0, &K1), value: &V) -> Result<(), StorageError> {
         let raw_key = Self::get_final_key(keys);
         let raw_value = codec::ser(value);
 
-        if let Err(err) = self.inner.put(raw_key, raw_value) {
-            log::error!("Cannot put value. error: {:?}", err);
-        }
+        Ok(self.inner.put(raw_key, raw_value)?)
     }
 
-    pub fn exists(&self, keys: (&K0, &K1)) -> bool {
+    /// Whether there is a committed value under the key, writes staged in a
+    /// [`Transaction`] are not seen
+    pub fn contains_key(&self, keys: (&K0, &K1)) -> Result<bool, StorageError> {
         let raw_key = Self::get_final_key(keys);
 
-        self.inner.key_may_exist(raw_key)
+        // the bloom filter has no false negatives, only a hit is read
+        if !self.inner.key_may_exist(&raw_key) {
+            return Ok(false);
+        }
+        Ok(self.inner.get_pinned(raw_key)?.is_some())
     }
 
-    pub fn remove(&self, keys: (&K0, &K1)) {
+    /// Removes the value, removing a missing key is not an error
+    pub fn remove(&self, keys: (&K0, &K1)) -> Result<(), StorageError> {
         let raw_key = Self::get_final_key(keys);
 
-        if let Err(err) = self.inner.delete(raw_key) {
-            log::error!("Cannot remove value. error: {:?}", err);
-        }
+        Ok(self.inner.delete(raw_key)?)
     }
 
-    pub fn get(&self, keys: (&K0, &K1)) -> Option<V> {
+    /// Value under the key, [`StorageError::Missing`] if there is none
+    pub fn get(&self, keys: (&K0, &K1)) -> Result<V, StorageError> {
         let raw_key = Self::get_final_key(keys);
-        let raw_value = match self.inner.get_pinned(&raw_key).ok().flatten() {
+        let raw_value = match self.inner.get_pinned(&raw_key)? {
             Some(raw_value) => raw_value,
-            None => {
-                log::error!("Value not found. key: {:?}", raw_key);
-                return None;
-            }
+            None => return Err(StorageError::Missing(raw_key)),
         };
 
-        match codec::de::<V>(&raw_value) {
-            Ok(value) => Some(value),
-            Err(err) => {
-                log::error!(
-                    "Cannot deserialize. raw_value: {:?}, error: {:?}",
-                    &raw_value[..],
-                    err
-                );
-                None
-            }
+        Ok(codec::de::<V>(&raw_value)?)
+    }
+
+    /// Stages writing the value into `tx`
+    ///
+    /// # Panics
+    /// If `tx` is a transaction of another database.
+    pub fn put_in(&self, tx: &mut Transaction, keys: (&K0, &K1), value: &V) {
+        assert!(tx.is_for(&self.inner), "transaction of another database");
+        tx.put(Self::get_final_key(keys), codec::ser(value));
+    }
+
+    /// Stages removing the value into `tx`
+    ///
+    /// # Panics
+    /// If `tx` is a transaction of another database.
+    pub fn remove_in(&self, tx: &mut Transaction, keys: (&K0, &K1)) {
+        assert!(tx.is_for(&self.inner), "transaction of another database");
+        tx.delete(Self::get_final_key(keys));
+    }
+
+    /// Whether there is a value under the key with the writes staged in `tx`
+    /// applied
+    ///
+    /// # Panics
+    /// If `tx` is a transaction of another database.
+    pub fn contains_key_in(
+        &self,
+        tx: &Transaction,
+        keys: (&K0, &K1),
+    ) -> Result<bool, StorageError> {
+        assert!(tx.is_for(&self.inner), "transaction of another database");
+        tx.contains(&Self::get_final_key(keys))
+    }
+
+    /// Value under the key with the writes staged in `tx` applied
+    ///
+    /// # Panics
+    /// If `tx` is a transaction of another database.
+    pub fn get_in(
+        &self,
+        tx: &Transaction,
+        keys: (&K0, &K1),
+    ) -> Result<V, StorageError> {
+        assert!(tx.is_for(&self.inner), "transaction of another database");
+        let raw_key = Self::get_final_key(keys);
+        match tx.get(&raw_key)? {
+            Some(raw_value) => Ok(codec::de::<V>(raw_value)?),
+            None => Err(StorageError::Missing(raw_key)),
         }
     }
 
-    pub fn iter<'a>(&'a self) -> impl Iterator<Item = (K0, K1, V)> + 'a {
-        self.inner
-            .prefix_iterator(INFO::prefix())
-            .filter_map(|result| {
-                let (raw_key, raw_value) = result.ok()?;
-                let keys = codec::de::<(K0, K1)>(&raw_key[32..]).ok()?;
-                let value = codec::de::<V>(raw_value).ok()?;
-                Some((keys.0, keys.1, value))
-            })
+    /// All entries in the order of the keys
+    pub fn iter(&self) -> Iter<'_, (K0, K1), V> {
+        Iter::new::<(K0, K1)>(
+            &self.inner,
+            &INFO::prefix(),
+            &..,
+            Direction::Forward,
+        )
+    }
+
+    /// All entries in the reverse order of the keys
+    pub fn iter_rev(&self) -> Iter<'_, (K0, K1), V> {
+        Iter::new::<(K0, K1)>(
+            &self.inner,
+            &INFO::prefix(),
+            &..,
+            Direction::Reverse,
+        )
+    }
+
+    /// Entries under the first key `key`, in the order of the second keys
+    pub fn iter_key(&self, key: &K0) -> Iter<'_, K1, V> {
+        self.range_key(key, ..)
+    }
+
+    /// Entries under the first key `key`, in the reverse order of the second
+    /// keys
+    pub fn iter_key_rev(&self, key: &K0) -> Iter<'_, K1, V> {
+        self.range_key_rev(key, ..)
+    }
+
+    /// Entries under the first key `key` with the second keys in `range`, in
+    /// the order of the second keys
+    pub fn range_key(
+        &self,
+        key: &K0,
+        range: impl RangeBounds<K1>,
+    ) -> Iter<'_, K1, V> {
+        let prefix = Self::get_semi_key(key);
+        Iter::new(&self.inner, &prefix, &range, Direction::Forward)
     }
 
-    pub fn iter_key<'a>(
-        &'a self,
+    /// Entries under the first key `key` with the second keys in `range`, in
+    /// the reverse order of the second keys
+    pub fn range_key_rev(
+        &self,
         key: &K0,
-    ) -> impl Iterator<Item = (K1, V)> + 'a {
-        self.inner
-            .prefix_iterator(Self::get_semi_key(key))
-            .filter_map(|result| {
-                let (raw_key, raw_value) = result.ok()?;
-                let (_, key) = codec::de::<(K0, K1)>(&raw_key[32..]).ok()?;
-                let value = codec::de::<V>(raw_value).ok()?;
-                Some((key, value))
-            })
+        range: impl RangeBounds<K1>,
+    ) -> Iter<'_, K1, V> {
+        let prefix = Self::get_semi_key(key);
+        Iter::new(&self.inner, &prefix, &range, Direction::Reverse)
+    }
+
+    /// Up to `limit` entries under the first key `key` after the `token` of
+    /// the previous page, or from the first one without a token
+    ///
+    /// # Panics
+    /// If `limit` is zero.
+    pub fn page_key(
+        &self,
+        key: &K0,
+        token: Option<&PageToken>,
+        limit: usize,
+    ) -> Result<Page<K1, V>, StorageError> {
+        let prefix = Self::get_semi_key(key);
+        Iter::from_token(&self.inner, &prefix, token, Direction::Forward)
+            .page(limit)
+    }
+
+    /// Up to `limit` entries under the first key `key` before the `token` of
+    /// the previous page, or from the last one without a token, in the
+    /// reverse order of the second keys
+    ///
+    /// # Panics
+    /// If `limit` is zero.
+    pub fn page_key_rev(
+        &self,
+        key: &K0,
+        token: Option<&PageToken>,
+        limit: usize,
+    ) -> Result<Page<K1, V>, StorageError> {
+        let prefix = Self::get_semi_key(key);
+        Iter::from_token(&self.inner, &prefix, token, Direction::Reverse)
+            .page(limit)
+    }
+}
+
+#[cfg(test)]
+mod storage_double_map_test {
+    use super::*;
+    use crate::test_db::TempDb;
+
+    struct ScoresInfo;
+    impl StorageInfo for ScoresInfo {
+        const STORAGE_NAME: &'static str = "Scores";
+    }
+
+    type Scores = StorageMap<String, u16, String, ScoresInfo>;
+
+    #[test]
+    fn put_get_remove() {
+        let db = TempDb::new("storage-double-map");
+        let scores = Scores::new(&db);
+        let (a, b) = (String::from("a"), String::from("b"));
+        scores.put((&a, &1), &"a1".into()).unwrap();
+        scores.put((&a, &2), &"a2".into()).unwrap();
+        scores.put((&b, &1), &"b1".into()).unwrap();
+        scores.put((&a, &1), &"a1'".into()).unwrap();
+        assert_eq!(scores.get((&a, &1)).unwrap(), "a1'");
+        assert_eq!(scores.get((&b, &1)).unwrap(), "b1");
+
+        scores.remove((&a, &1)).unwrap();
+        scores.remove((&b, &2)).unwrap();
+        assert!(scores.get((&a, &1)).unwrap_err().is_missing());
+        assert_eq!(scores.get((&a, &2)).unwrap(), "a2");
+        assert_eq!(scores.get((&b, &1)).unwrap(), "b1");
+    }
+
+    #[test]
+    fn contains_key() {
+        let db = TempDb::new("storage-double-map-contains");
+        let scores = Scores::new(&db);
+        let (a, b) = (String::from("a"), String::from("b"));
+        scores.put((&a, &1), &"a1".into()).unwrap();
+        scores.put((&a, &2), &"a2".into()).unwrap();
+        scores.remove((&a, &2)).unwrap();
+        assert!(scores.contains_key((&a, &1)).unwrap());
+        assert!(!scores.contains_key((&a, &2)).unwrap());
+        assert!(!scores.contains_key((&b, &1)).unwrap());
+        assert!(!scores.contains_key((&a, &0)).unwrap());
+
+        // a value, that does not decode, is still present
+        db.put(Scores::get_final_key((&b, &1)), [0xff]).unwrap();
+        assert!(scores.contains_key((&b, &1)).unwrap());
+    }
+
+    #[test]
+    fn missing_and_corrupted() {
+        let db = TempDb::new("storage-double-map-errors");
+        let scores = Scores::new(&db);
+        let a = String::from("a");
+        match scores.get((&a, &1)) {
+            Err(StorageError::Missing(raw_key)) => {
+                assert_eq!(raw_key, Scores::get_final_key((&a, &1)))
+            }
+            other => panic!("expected a missing value, got {:?}", other),
+        }
+
+        // a length prefix without the bytes it promises
+        db.put(Scores::get_final_key((&a, &1)), [0xff]).unwrap();
+        let err = scores.get((&a, &1)).unwrap_err();
+        assert!(matches!(err, StorageError::Decode(_)), "{:?}", err);
+        assert!(!err.is_missing());
     }
 }
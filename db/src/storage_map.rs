@@ -1,9 +1,25 @@
-use std::{marker::PhantomData, sync::Arc};
+use std::{
+    marker::PhantomData,
+    ops::{Bound, RangeBounds},
+    sync::Arc,
+};
 
+use rocksdb::Direction;
 use serde::{de::DeserializeOwned, Serialize};
 
-use crate::{codec, StorageInfo};
+use crate::{
+    codec,
+    error::StorageError,
+    iter::{Iter, Page, PageToken},
+    transaction::Transaction,
+    StorageInfo,
+};
 
+/// Typed storage under the prefix of `INFO`
+///
+/// Only `get_in` and `contains_key_in` see the writes staged in a
+/// [`Transaction`], the other reads, like `get`, `contains_key`, the
+/// iterators and the pages, see the committed database.
 pub struct StorageMap<K, V, INFO> {
     inner: Arc<rocksdb::DB>,
     _phantom: PhantomData<(K, V, INFO)>,
@@ -23,64 +39,215 @@ impl<
     }
 
     fn get_final_key(key: &K) -> Vec<u8> {
-        let raw_key = codec::ser(key);
+        let raw_key = codec::ser_key(key);
         [&INFO::prefix()[..], &raw_key].concat()
     }
 
-    pub fn put(&self, key: &K, value: &V) {
+    pub fn put(&self, key: &K, value: &V) -> Result<(), StorageError> {
         let raw_key = Self::get_final_key(key);
         let raw_value = codec::ser(value);
 
-        if let Err(err) = self.inner.put(raw_key, raw_value) {
-            log::error!("Cannot put value. error: {:?}", err);
-        }
+        Ok(self.inner.put(raw_key, raw_value)?)
     }
 
-    pub fn exists(&self, key: &K) -> bool {
+    /// Whether there is a committed value under the key, writes staged in a
+    /// [`Transaction`] are not seen
+    pub fn contains_key(&self, key: &K) -> Result<bool, StorageError> {
         let raw_key = Self::get_final_key(key);
 
-        self.inner.key_may_exist(raw_key)
+        // the bloom filter has no false negatives, only a hit is read
+        if !self.inner.key_may_exist(&raw_key) {
+            return Ok(false);
+        }
+        Ok(self.inner.get_pinned(raw_key)?.is_some())
     }
 
-    pub fn remove(&self, key: &K) {
+    /// Removes the value, removing a missing key is not an error
+    pub fn remove(&self, key: &K) -> Result<(), StorageError> {
         let raw_key = Self::get_final_key(key);
 
-        if let Err(err) = self.inner.delete(raw_key) {
-            log::error!("Cannot remove value. error: {:?}", err);
-        }
+        Ok(self.inner.delete(raw_key)?)
     }
 
-    pub fn get(&self, key: &K) -> Option<V> {
+    /// Value under the key, [`StorageError::Missing`] if there is none
+    pub fn get(&self, key: &K) -> Result<V, StorageError> {
         let raw_key = Self::get_final_key(key);
-        let raw_value = match self.inner.get_pinned(&raw_key).ok().flatten() {
+        let raw_value = match self.inner.get_pinned(&raw_key)? {
             Some(raw_value) => raw_value,
-            None => {
-                log::error!("Value not found. key: {:?}", raw_key);
-                return None;
-            }
+            None => return Err(StorageError::Missing(raw_key)),
         };
 
-        match codec::de::<V>(&raw_value) {
-            Ok(value) => Some(value),
-            Err(err) => {
-                log::error!(
-                    "Cannot deserialize. raw_value: {:?}, error: {:?}",
-                    &raw_value[..],
-                    err
-                );
-                None
-            }
+        Ok(codec::de::<V>(&raw_value)?)
+    }
+
+    /// Stages writing the value into `tx`
+    ///
+    /// # Panics
+    /// If `tx` is a transaction of another database.
+    pub fn put_in(&self, tx: &mut Transaction, key: &K, value: &V) {
+        assert!(tx.is_for(&self.inner), "transaction of another database");
+        tx.put(Self::get_final_key(key), codec::ser(value));
+    }
+
+    /// Stages removing the value into `tx`
+    ///
+    /// # Panics
+    /// If `tx` is a transaction of another database.
+    pub fn remove_in(&self, tx: &mut Transaction, key: &K) {
+        assert!(tx.is_for(&self.inner), "transaction of another database");
+        tx.delete(Self::get_final_key(key));
+    }
+
+    /// Whether there is a value under the key with the writes staged in `tx`
+    /// applied
+    ///
+    /// # Panics
+    /// If `tx` is a transaction of another database.
+    pub fn contains_key_in(
+        &self,
+        tx: &Transaction,
+        key: &K,
+    ) -> Result<bool, StorageError> {
+        assert!(tx.is_for(&self.inner), "transaction of another database");
+        tx.contains(&Self::get_final_key(key))
+    }
+
+    /// Value under the key with the writes staged in `tx` applied
+    ///
+    /// # Panics
+    /// If `tx` is a transaction of another database.
+    pub fn get_in(&self, tx: &Transaction, key: &K) -> Result<V, StorageError> {
+        assert!(tx.is_for(&self.inner), "transaction of another database");
+        let raw_key = Self::get_final_key(key);
+        match tx.get(&raw_key)? {
+            Some(raw_value) => Ok(codec::de::<V>(raw_value)?),
+            None => Err(StorageError::Missing(raw_key)),
         }
     }
 
-    pub fn iter<'a>(&'a self) -> impl Iterator<Item = (K, V)> + 'a {
-        self.inner
-            .prefix_iterator(INFO::prefix())
-            .filter_map(|result| {
-                let (raw_key, raw_value) = result.ok()?;
-                let key = codec::de::<K>(&raw_key[32..]).ok()?;
-                let value = codec::de::<V>(raw_value).ok()?;
-                Some((key, value))
-            })
+    /// All entries in the order of the keys
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        self.range(..)
+    }
+
+    /// All entries in the reverse order of the keys
+    pub fn iter_rev(&self) -> Iter<'_, K, V> {
+        self.range_rev(..)
+    }
+
+    /// Entries from `key` on, in the order of the keys
+    pub fn iter_from(&self, key: &K) -> Iter<'_, K, V> {
+        self.range((Bound::Included(key), Bound::Unbounded))
+    }
+
+    /// Entries with the keys in `range`, in the order of the keys
+    pub fn range(&self, range: impl RangeBounds<K>) -> Iter<'_, K, V> {
+        Iter::new(&self.inner, &INFO::prefix(), &range, Direction::Forward)
+    }
+
+    /// Entries with the keys in `range`, in the reverse order of the keys
+    pub fn range_rev(&self, range: impl RangeBounds<K>) -> Iter<'_, K, V> {
+        Iter::new(&self.inner, &INFO::prefix(), &range, Direction::Reverse)
+    }
+
+    /// Up to `limit` entries after the `token` of the previous page, or from
+    /// the first one without a token
+    ///
+    /// # Panics
+    /// If `limit` is zero.
+    pub fn page(
+        &self,
+        token: Option<&PageToken>,
+        limit: usize,
+    ) -> Result<Page<K, V>, StorageError> {
+        Iter::from_token(
+            &self.inner,
+            &INFO::prefix(),
+            token,
+            Direction::Forward,
+        )
+        .page(limit)
+    }
+
+    /// Up to `limit` entries before the `token` of the previous page, or from
+    /// the last one without a token, in the reverse order of the keys
+    ///
+    /// # Panics
+    /// If `limit` is zero.
+    pub fn page_rev(
+        &self,
+        token: Option<&PageToken>,
+        limit: usize,
+    ) -> Result<Page<K, V>, StorageError> {
+        Iter::from_token(
+            &self.inner,
+            &INFO::prefix(),
+            token,
+            Direction::Reverse,
+        )
+        .page(limit)
+    }
+}
+
+#[cfg(test)]
+mod storage_map_test {
+    use super::*;
+    use crate::test_db::TempDb;
+
+    struct NamesInfo;
+    impl StorageInfo for NamesInfo {
+        const STORAGE_NAME: &'static str = "Names";
+    }
+
+    type Names = StorageMap<u32, String, NamesInfo>;
+
+    #[test]
+    fn put_get_remove() {
+        let db = TempDb::new("storage-map");
+        let names = Names::new(&db);
+        names.put(&1, &"a".into()).unwrap();
+        names.put(&2, &"b".into()).unwrap();
+        names.put(&1, &"c".into()).unwrap();
+        assert_eq!(names.get(&1).unwrap(), "c");
+        assert_eq!(names.get(&2).unwrap(), "b");
+
+        names.remove(&1).unwrap();
+        names.remove(&3).unwrap();
+        assert!(names.get(&1).unwrap_err().is_missing());
+        assert_eq!(names.get(&2).unwrap(), "b");
+    }
+
+    #[test]
+    fn contains_key() {
+        let db = TempDb::new("storage-map-contains");
+        let names = Names::new(&db);
+        names.put(&1, &"a".into()).unwrap();
+        names.put(&2, &"b".into()).unwrap();
+        names.remove(&2).unwrap();
+        assert!(names.contains_key(&1).unwrap());
+        assert!(!names.contains_key(&2).unwrap());
+        assert!(!names.contains_key(&3).unwrap());
+
+        // a value, that does not decode, is still present
+        db.put(Names::get_final_key(&3), [0xff]).unwrap();
+        assert!(names.contains_key(&3).unwrap());
+    }
+
+    #[test]
+    fn missing_and_corrupted() {
+        let db = TempDb::new("storage-map-errors");
+        let names = Names::new(&db);
+        match names.get(&1) {
+            Err(StorageError::Missing(raw_key)) => {
+                assert_eq!(raw_key, Names::get_final_key(&1))
+            }
+            other => panic!("expected a missing value, got {:?}", other),
+        }
+
+        // a length prefix without the bytes it promises
+        db.put(Names::get_final_key(&1), [0xff]).unwrap();
+        let err = names.get(&1).unwrap_err();
+        assert!(matches!(err, StorageError::Decode(_)), "{:?}", err);
+        assert!(!err.is_missing());
     }
 }
@@ -0,0 +1,172 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use rocksdb::WriteBatch;
+
+use crate::error::StorageError;
+
+/// Writes to several storages, that are committed atomically
+///
+/// Storages stage their writes into the transaction with the `*_in`
+/// methods, and the point reads through it, `get_in` and `contains_key_in`,
+/// see the staged writes before the database. Iteration and paging read the
+/// committed database only. Nothing is written until
+/// [`Transaction::commit`], which applies the whole batch at once, dropping
+/// the transaction discards it.
+///
+/// The transaction does not lock the keys it reads, so a concurrent write
+/// between a read and the commit is not detected.
+pub struct Transaction {
+    inner: Arc<rocksdb::DB>,
+    /// Staged values by raw key, `None` for a removal
+    staged: BTreeMap<Vec<u8>, Option<Vec<u8>>>,
+}
+
+impl Transaction {
+    pub fn new(rocks_db: &Arc<rocksdb::DB>) -> Self {
+        Self {
+            inner: rocks_db.clone(),
+            staged: BTreeMap::new(),
+        }
+    }
+
+    /// Number of the keys written, a key written twice counts once
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.staged.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.staged.is_empty()
+    }
+
+    /// Whether the transaction writes to `rocks_db`
+    pub(crate) fn is_for(&self, rocks_db: &Arc<rocksdb::DB>) -> bool {
+        Arc::ptr_eq(&self.inner, rocks_db)
+    }
+
+    pub(crate) fn put(&mut self, raw_key: Vec<u8>, raw_value: Vec<u8>) {
+        self.staged.insert(raw_key, Some(raw_value));
+    }
+
+    pub(crate) fn delete(&mut self, raw_key: Vec<u8>) {
+        self.staged.insert(raw_key, None);
+    }
+
+    /// Staged value under the raw key, or the committed one if the key is
+    /// not written in the transaction
+    pub(crate) fn get(
+        &self,
+        raw_key: &[u8],
+    ) -> Result<Option<Vec<u8>>, StorageError> {
+        match self.staged.get(raw_key) {
+            Some(staged) => Ok(staged.clone()),
+            None => Ok(self.inner.get_pinned(raw_key)?.map(|raw| raw.to_vec())),
+        }
+    }
+
+    /// Whether there is a staged or a committed value under the raw key, a
+    /// staged removal hides the committed value
+    pub(crate) fn contains(
+        &self,
+        raw_key: &[u8],
+    ) -> Result<bool, StorageError> {
+        match self.staged.get(raw_key) {
+            Some(staged) => Ok(staged.is_some()),
+            None => Ok(self.inner.get_pinned(raw_key)?.is_some()),
+        }
+    }
+
+    /// Applies all of the staged writes atomically
+    pub fn commit(self) -> Result<(), StorageError> {
+        let mut batch = WriteBatch::default();
+        for (raw_key, raw_value) in self.staged {
+            match raw_value {
+                Some(raw_value) => batch.put(raw_key, raw_value),
+                None => batch.delete(raw_key),
+            }
+        }
+        Ok(self.inner.write(batch)?)
+    }
+}
+
+#[cfg(test)]
+mod transaction_test {
+    use super::*;
+    use crate::{
+        storage_double_map, storage_map, test_db::TempDb, StorageInfo,
+    };
+
+    struct NamesInfo;
+    impl StorageInfo for NamesInfo {
+        const STORAGE_NAME: &'static str = "Names";
+    }
+
+    struct IndexInfo;
+    impl StorageInfo for IndexInfo {
+        const STORAGE_NAME: &'static str = "Index";
+    }
+
+    type Names = storage_map::StorageMap<u32, String, NamesInfo>;
+    type Index = storage_double_map::StorageMap<String, u32, (), IndexInfo>;
+
+    /// Stages renaming 1 to "b" and adding 2 as "c"
+    fn stage(db: &TempDb, names: &Names, index: &Index) -> Transaction {
+        let mut tx = Transaction::new(db);
+        names.put_in(&mut tx, &1, &"b".into());
+        index.remove_in(&mut tx, (&"a".into(), &1));
+        index.put_in(&mut tx, (&"b".into(), &1), &());
+        names.put_in(&mut tx, &2, &"c".into());
+        index.put_in(&mut tx, (&"c".into(), &2), &());
+
+        // read your writes, including a removal
+        assert_eq!(names.get_in(&tx, &1).unwrap(), "b");
+        assert_eq!(names.get_in(&tx, &2).unwrap(), "c");
+        assert!(index
+            .get_in(&tx, (&"a".into(), &1))
+            .unwrap_err()
+            .is_missing());
+        index.get_in(&tx, (&"b".into(), &1)).unwrap();
+        assert!(names.contains_key_in(&tx, &2).unwrap());
+        assert!(!names.contains_key_in(&tx, &3).unwrap());
+        assert!(!index.contains_key_in(&tx, (&"a".into(), &1)).unwrap());
+        assert!(index.contains_key_in(&tx, (&"c".into(), &2)).unwrap());
+        // iteration reads the committed database only
+        assert_eq!(index_keys(index), [("a".into(), 1)]);
+        // nothing is written yet
+        assert_eq!(names.get(&1).unwrap(), "a");
+        assert!(!names.contains_key(&2).unwrap());
+        assert_eq!(tx.len(), 5);
+        tx
+    }
+
+    fn index_keys(index: &Index) -> Vec<(String, u32)> {
+        index.iter().map(|entry| entry.unwrap().0).collect()
+    }
+
+    #[test]
+    fn commit_and_drop() {
+        let db = TempDb::new("transaction");
+        let (names, index) = (Names::new(&db), Index::new(&db));
+        names.put(&1, &"a".into()).unwrap();
+        index.put((&"a".into(), &1), &()).unwrap();
+
+        drop(stage(&db, &names, &index));
+        assert_eq!(names.get(&1).unwrap(), "a");
+        assert!(names.get(&2).unwrap_err().is_missing());
+        assert_eq!(index_keys(&index), [("a".into(), 1)]);
+
+        stage(&db, &names, &index).commit().unwrap();
+        assert_eq!(names.get(&1).unwrap(), "b");
+        assert_eq!(names.get(&2).unwrap(), "c");
+        assert_eq!(index_keys(&index), [("b".into(), 1), ("c".into(), 2)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "transaction of another database")]
+    fn another_database() {
+        let (db, other) = (TempDb::new("tx-this"), TempDb::new("tx-other"));
+        let tx = Transaction::new(&other);
+        let _ = Names::new(&db).get_in(&tx, &1);
+    }
+}
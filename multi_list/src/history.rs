@@ -0,0 +1,237 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display, Formatter},
+    mem::replace,
+};
+
+use crate::{
+    list::{MultiList, NodeIter, NodeIterFinite},
+    pointer::{PointerKind, RcKind},
+};
+
+/// Error of the [`History`] operations on branches
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HistoryError {
+    /// There is no branch with this name
+    Missing(String),
+    /// A branch with this name already exists
+    Exists(String),
+    /// The checked out branch can not be removed
+    CheckedOut(String),
+}
+
+impl Display for HistoryError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            HistoryError::Missing(name) => write!(f, "no branch `{}`", name),
+            HistoryError::Exists(name) => {
+                write!(f, "branch `{}` already exists", name)
+            }
+            HistoryError::CheckedOut(name) => {
+                write!(f, "branch `{}` is checked out", name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for HistoryError {}
+
+struct Branch<T, P: PointerKind> {
+    /// Applied edits, the latest on top
+    done: MultiList<T, P>,
+    /// States before the undone edits, the latest undo on top, so a redo
+    /// restores the same nodes and branches still share them
+    undone: Vec<MultiList<T, P>>,
+}
+
+impl<T, P: PointerKind> Branch<T, P> {
+    fn new(done: MultiList<T, P>) -> Self {
+        Self {
+            done,
+            undone: Vec::new(),
+        }
+    }
+}
+
+/// Edits since the latest common edit of two branches, see
+/// [`History::diff`]
+pub struct Diff<'a, T, P: PointerKind = RcKind> {
+    /// Edits of the first branch, the latest first
+    pub ours: NodeIterFinite<'a, T, P>,
+    /// Edits of the second branch, the latest first
+    pub theirs: NodeIterFinite<'a, T, P>,
+}
+
+/// Undo and redo history of edits with named branches
+///
+/// Every branch is a [`MultiList`] of edits, branches share the edits made
+/// before they were forked, so forking takes `O(1)` and the latest common
+/// edit of two branches is found by [`MultiList::common`].
+///
+/// ```
+/// use multi_list::history::History;
+///
+/// let mut history = History::new();
+/// history.record("type a");
+/// history.branch("draft").unwrap();
+/// history.checkout("draft").unwrap();
+/// history.record("type b");
+/// assert_eq!(history.undo(), Some(&"type b"));
+/// assert_eq!(history.redo(), Some(&"type b"));
+///
+/// let diff = history.diff("draft", History::<&str>::MAIN).unwrap();
+/// assert_eq!(diff.ours.collect::<Vec<_>>(), [&"type b"]);
+/// assert_eq!(diff.theirs.count(), 0);
+/// ```
+pub struct History<T, P: PointerKind = RcKind> {
+    branches: BTreeMap<String, Branch<T, P>>,
+    current: String,
+}
+
+impl<T, P: PointerKind> Default for History<T, P> {
+    fn default() -> Self {
+        let mut branches = BTreeMap::new();
+        branches
+            .insert(Self::MAIN.to_string(), Branch::new(MultiList::default()));
+        Self {
+            branches,
+            current: Self::MAIN.to_string(),
+        }
+    }
+}
+
+impl<T> History<T> {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<T, P: PointerKind> History<T, P> {
+    /// Branch, that a new history starts with
+    pub const MAIN: &'static str = "main";
+
+    fn branch_of(&self, name: &str) -> Result<&Branch<T, P>, HistoryError> {
+        self.branches
+            .get(name)
+            .ok_or_else(|| HistoryError::Missing(name.to_string()))
+    }
+
+    fn checked_out(&mut self) -> &mut Branch<T, P> {
+        self.branches
+            .get_mut(&self.current)
+            .expect("checked out branch exists")
+    }
+
+    /// Name of the checked out branch
+    #[inline]
+    pub fn current(&self) -> &str {
+        &self.current
+    }
+
+    /// Names of all branches in order
+    pub fn branches(&self) -> impl Iterator<Item = &str> + '_ {
+        self.branches.keys().map(String::as_str)
+    }
+
+    /// Applied edits of the checked out branch, the latest on top
+    #[inline]
+    pub fn edits(&self) -> &MultiList<T, P> {
+        &self.branches[&self.current].done
+    }
+
+    /// Applied edits of the branch `name`, the latest on top
+    pub fn edits_of(
+        &self,
+        name: &str,
+    ) -> Result<&MultiList<T, P>, HistoryError> {
+        Ok(&self.branch_of(name)?.done)
+    }
+
+    /// Applies a new edit to the checked out branch, which drops the
+    /// undone edits
+    pub fn record(&mut self, edit: T) {
+        let branch = self.checked_out();
+        branch.undone.clear();
+        branch.done.push(edit);
+    }
+
+    /// Undoes the latest edit of the checked out branch and returns it
+    pub fn undo(&mut self) -> Option<&T> {
+        let branch = self.checked_out();
+        if branch.done.is_empty() {
+            return None;
+        }
+        let prev = branch.done.tail();
+        let done = replace(&mut branch.done, prev);
+        branch.undone.push(done);
+        branch.undone.last()?.peek()
+    }
+
+    /// Applies the latest undone edit of the checked out branch again and
+    /// returns it
+    pub fn redo(&mut self) -> Option<&T> {
+        let branch = self.checked_out();
+        branch.done = branch.undone.pop()?;
+        branch.done.peek()
+    }
+
+    /// Forks a new branch `name` from the applied edits of the checked out
+    /// one, without checking it out
+    pub fn branch(
+        &mut self,
+        name: impl Into<String>,
+    ) -> Result<(), HistoryError> {
+        let name = name.into();
+        if self.branches.contains_key(&name) {
+            return Err(HistoryError::Exists(name));
+        }
+        let done = self.edits().branch();
+        self.branches.insert(name, Branch::new(done));
+        Ok(())
+    }
+
+    pub fn checkout(&mut self, name: &str) -> Result<(), HistoryError> {
+        self.branch_of(name)?;
+        self.current = name.to_string();
+        Ok(())
+    }
+
+    /// Removes the branch `name`, that is not checked out, the edits shared
+    /// with other branches are kept
+    pub fn remove_branch(&mut self, name: &str) -> Result<(), HistoryError> {
+        if name == self.current {
+            return Err(HistoryError::CheckedOut(name.to_string()));
+        }
+        self.branches
+            .remove(name)
+            .map(drop)
+            .ok_or_else(|| HistoryError::Missing(name.to_string()))
+    }
+
+    /// Latest edit, that both branches have applied, as in the lowest common
+    /// ancestor of two versions
+    pub fn common(
+        &self,
+        ours: &str,
+        theirs: &str,
+    ) -> Result<NodeIter<'_, T, P>, HistoryError> {
+        let (ours, theirs) = (self.edits_of(ours)?, self.edits_of(theirs)?);
+        Ok(ours.common(theirs))
+    }
+
+    /// Edits, that each of the branches has applied since their latest common
+    /// edit
+    pub fn diff(
+        &self,
+        ours: &str,
+        theirs: &str,
+    ) -> Result<Diff<'_, T, P>, HistoryError> {
+        let (ours, theirs) = (self.edits_of(ours)?, self.edits_of(theirs)?);
+        let common = ours.common(theirs);
+        Ok(Diff {
+            ours: ours.iter_to(common.clone()),
+            theirs: theirs.iter_to(common),
+        })
+    }
+}
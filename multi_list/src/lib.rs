@@ -1,8 +1,10 @@
+pub mod history;
 pub mod list;
+pub mod pointer;
 
 #[cfg(test)]
 mod list_test {
-    use crate::list::MultiList;
+    use crate::list::{MultiList, Shared, SyncMultiList};
 
     #[test]
     fn push_pop() {
@@ -17,7 +19,8 @@ mod list_test {
 
         assert_eq!(branch.pop(), Some("Goodbye,"));
         assert_eq!(branch.pop(), Some(" cruel"));
-        assert_ne!(branch.pop(), Some(" world!"));
+        assert_eq!(branch.pop(), Some(" world!"));
+        assert_eq!(branch.pop(), Some(" heh!"));
         assert_eq!(branch.pop(), None);
 
         assert_eq!(master.pop(), Some("Hello,"));
@@ -28,6 +31,7 @@ mod list_test {
 
     use std::{
         cmp::max,
+        hash::BuildHasher,
         ops::{Add, Mul},
     };
 
@@ -87,4 +91,254 @@ mod list_test {
         assert_eq!(node_b, branch.find(&"B"));
         assert_eq!(node_b, MultiList::common(&master, &branch));
     }
+
+    #[test]
+    fn append() {
+        let mut master = MultiList::new();
+        master.push("A").push("B");
+        let mut other = MultiList::new();
+        other.push("C").push("D");
+
+        master.append(&other).append(&MultiList::new());
+        assert_eq!(master.iter().collect::<Vec<_>>(), [&"D", &"C", &"B", &"A"]);
+        assert_eq!(other.iter().collect::<Vec<_>>(), [&"D", &"C"]);
+
+        let mut empty = MultiList::new();
+        empty.append(&other);
+        assert_eq!(empty.find(&"C"), other.find(&"C"));
+    }
+
+    #[test]
+    fn sync_branches() {
+        fn send_sync<T: Send + Sync>(_: &T) {}
+
+        let mut master = SyncMultiList::default();
+        master.push("A").push("B");
+        send_sync(&master);
+
+        let workers = (0..4)
+            .map(|i| {
+                let mut branch = master.branch();
+                std::thread::spawn(move || {
+                    branch.push(["C", "D", "E", "F"][i]);
+                    branch
+                })
+            })
+            .collect::<Vec<_>>();
+        master.push("G");
+
+        for (worker, item) in workers.into_iter().zip(["C", "D", "E", "F"]) {
+            let mut branch = worker.join().unwrap();
+            let node_b = master.find(&"B");
+            assert_eq!(node_b, branch.find(&"B"));
+            assert_eq!(node_b, SyncMultiList::common(&master, &branch));
+            assert_eq!(branch.pop(), Some(item));
+        }
+        assert_eq!(master.pop(), Some("G"));
+        assert_eq!(master.pop(), Some("B"));
+        assert_eq!(master.pop(), Some("A"));
+        assert_eq!(master.pop(), None);
+    }
+
+    #[test]
+    fn drop_long_list() {
+        const LEN: usize = 1 << 21;
+
+        let mut master = MultiList::new();
+        for i in 0..LEN {
+            master.push(i);
+        }
+        let mut branch = master.branch();
+        branch.push(LEN);
+        for i in 0..LEN {
+            master.push(i);
+        }
+        // only the nodes above the shared tail are freed
+        drop(master);
+        assert_eq!(branch.pop(), Some(LEN));
+        assert_eq!(branch.iter().count(), LEN);
+        drop(branch);
+
+        let mut master = SyncMultiList::default();
+        for i in 0..LEN {
+            master.push(i);
+        }
+        let branches = (0..4).map(|_| master.branch()).collect::<Vec<_>>();
+        drop(master);
+        let workers = branches
+            .into_iter()
+            .map(|branch| std::thread::spawn(move || drop(branch)));
+        workers.for_each(|worker| worker.join().unwrap());
+    }
+
+    #[test]
+    fn persistent_stack() {
+        let list = MultiList::<i32>::from_top(1..=5);
+        assert_eq!(list.len(), 5);
+        assert_eq!(list.peek(), Some(&1));
+        assert!((&list).into_iter().eq([1, 2, 3, 4, 5].iter()));
+
+        let mut stack = MultiList::new();
+        assert!(stack.is_empty() && stack.peek().is_none());
+        stack.extend(1..=5);
+        assert_eq!(stack.peek(), Some(&5));
+        // `collect` pushes as `extend` does
+        assert_eq!((1..=5).collect::<MultiList<_>>(), stack);
+        assert_eq!(stack.reverse(), list);
+        assert_eq!(list.reverse().reverse(), list);
+
+        let strings = list.map(|i| i.to_string());
+        assert!(strings.iter().eq(["1", "2", "3", "4", "5"].iter()));
+
+        let odd = list.filter(|i| i % 2 == 1);
+        assert!(odd.iter().eq([1, 3, 5].iter()));
+        assert_eq!(odd.len(), 3);
+        // the tail after the last removed item is shared
+        assert_eq!(odd.find(&5), list.find(&5));
+        assert_eq!(list.filter(|_| true).common(&list), list.iter());
+        assert!(list.filter(|_| false).is_empty());
+
+        let mut copy = list.clone();
+        assert_eq!(copy.common(&list), list.iter());
+        copy.push(0);
+        assert_ne!(copy, list);
+        assert_eq!(copy.len(), list.len() + 1);
+        assert_eq!(copy.pop(), Some(0));
+        assert_eq!(copy, list);
+        assert_ne!(list, MultiList::from_top(1..=4));
+        assert_ne!(list, MultiList::from_top(2..=6));
+
+        let state = std::collections::hash_map::RandomState::new();
+        let hash = |list: &MultiList<i32>| state.hash_one(list);
+        assert_eq!(hash(&list), hash(&stack.reverse()));
+        assert_ne!(hash(&list), hash(&stack));
+    }
+
+    #[test]
+    fn try_pop() {
+        let mut master = MultiList::new();
+        master.push(String::from("A")).push(String::from("B"));
+
+        let mut branch = master.branch();
+        branch.push(String::from("C"));
+        assert_eq!(branch.try_pop(), Ok(Some(String::from("C"))));
+        assert_eq!(branch.try_pop(), Err(Shared));
+        assert_eq!(branch.peek().map(String::as_str), Some("B"));
+
+        assert_eq!(master.pop().as_deref(), Some("B"));
+        drop(master);
+        assert_eq!(branch.try_pop(), Ok(Some(String::from("B"))));
+        assert_eq!(branch.try_pop(), Ok(Some(String::from("A"))));
+        assert_eq!(branch.try_pop(), Ok(None));
+    }
+
+    #[test]
+    fn evaluate_closure() {
+        let list = MultiList::<i32>::from_top(1..=4);
+        let mut seen = Vec::new();
+        let sum = list.evaluate(
+            |sum, item| {
+                seen.push(*item);
+                sum + item
+            },
+            0,
+        );
+        assert_eq!(sum, 10);
+        assert_eq!(seen, [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn history() {
+        use crate::history::{History, HistoryError};
+
+        let mut history = History::new();
+        history.record(1);
+        history.record(2);
+        history.branch("feature").unwrap();
+        assert_eq!(
+            history.branch("feature"),
+            Err(HistoryError::Exists("feature".to_string()))
+        );
+        assert_eq!(
+            history.checkout("nope"),
+            Err(HistoryError::Missing("nope".to_string()))
+        );
+
+        history.record(3);
+        history.checkout("feature").unwrap();
+        assert_eq!(history.current(), "feature");
+        history.record(4);
+        history.record(5);
+
+        assert_eq!(history.undo(), Some(&5));
+        assert_eq!(history.undo(), Some(&4));
+        assert!(history.edits().iter().eq([2, 1].iter()));
+        assert_eq!(history.redo(), Some(&4));
+        history.record(6);
+        assert_eq!(history.redo(), None);
+        assert!(history.edits().iter().eq([6, 4, 2, 1].iter()));
+
+        // undo and redo keep the nodes shared with the other branches
+        assert_eq!(history.undo(), Some(&6));
+        assert_eq!(history.undo(), Some(&4));
+        assert_eq!(history.redo(), Some(&4));
+        let common = history.common("feature", History::<i32>::MAIN).unwrap();
+        assert_eq!(common.clone().next(), Some(&2));
+        assert_eq!(common, history.edits_of("main").unwrap().find(&2));
+
+        let diff = history.diff("feature", "main").unwrap();
+        assert_eq!(diff.ours.collect::<Vec<_>>(), [&4]);
+        assert_eq!(diff.theirs.collect::<Vec<_>>(), [&3]);
+
+        assert_eq!(
+            history.remove_branch("feature"),
+            Err(HistoryError::CheckedOut("feature".to_string()))
+        );
+        history.checkout("main").unwrap();
+        history.remove_branch("feature").unwrap();
+        assert!(history.branches().eq(["main"]));
+        while history.undo().is_some() {}
+        assert!(history.edits().is_empty());
+        assert_eq!(history.undo(), None);
+        assert_eq!(history.redo(), Some(&1));
+    }
+
+    #[test]
+    fn interned() {
+        use crate::list::Interner;
+
+        let mut interner = Interner::new();
+        let a = interner.collect(["x", "y", "z"]);
+        let mut b = interner.collect(["w", "y", "z"]);
+        assert_eq!(interner.len(), 4);
+
+        // separately built lists share their equal suffixes
+        assert_eq!(a.find(&"y"), b.find(&"y"));
+        assert_eq!(MultiList::common(&a, &b), a.find(&"y"));
+        assert_eq!(interner.collect(["x", "y", "z"]).common(&a), a.iter());
+        let separate = MultiList::<_>::from_top(vec!["x", "y", "z"]);
+        assert_ne!(separate.common(&a), a.iter());
+
+        b.push_interned("v", &mut interner);
+        let mut c = a.tail();
+        c.push_interned("w", &mut interner)
+            .push_interned("v", &mut interner);
+        assert_eq!(b.iter(), c.iter());
+        assert_eq!(interner.len(), 5);
+
+        drop((a, b, c));
+        assert_eq!(interner.len(), 0);
+        interner.purge();
+        assert!(interner.is_empty());
+
+        let mut interner = Interner::<_, crate::pointer::ArcKind>::default();
+        let lists = (0..4)
+            .map(|_| interner.collect(0..1000))
+            .collect::<Vec<SyncMultiList<_>>>();
+        assert_eq!(interner.len(), 1000);
+        for list in lists {
+            std::thread::spawn(move || drop(list)).join().unwrap();
+        }
+        assert!(interner.is_empty());
+    }
 }
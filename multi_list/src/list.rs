@@ -1,28 +1,49 @@
 use std::{
-    fmt::{Display, Formatter, Result},
+    collections::{hash_map::RandomState, HashMap},
+    fmt::{Debug, Display, Formatter, Result},
+    hash::{BuildHasher, Hash, Hasher},
+    iter::FromIterator,
     mem::take,
     ops::Deref,
-    rc::Rc,
 };
 
-type Ref<T> = Option<Rc<T>>;
+use crate::pointer::{ArcKind, PointerKind, RcKind};
 
-#[derive(Debug, Default)]
-struct Node<T> {
+type Ref<T, P> = Option<<P as PointerKind>::Pointer<Node<T, P>>>;
+type Weak<T, P> = <P as PointerKind>::Weak<Node<T, P>>;
+
+struct Node<T, P: PointerKind = RcKind> {
     item: T,
-    next: Ref<Node<T>>,
+    next: Ref<T, P>,
+    /// Number of nodes from this one to the end of the list
+    len: usize,
+}
+
+impl<T: Debug, P: PointerKind> Debug for Node<T, P> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        f.debug_struct("Node")
+            .field("item", &self.item)
+            .field("next", &self.next.as_deref())
+            .finish()
+    }
 }
 
-impl<T> PartialEq for &Node<T> {
+impl<T, P: PointerKind> PartialEq for &Node<T, P> {
     fn eq(&self, other: &Self) -> bool {
-        std::ptr::eq((*self) as *const Node<T>, (*other) as *const Node<T>)
+        std::ptr::eq(*self, *other)
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct NodeIter<'a, T>(Option<&'a Node<T>>);
+#[derive(Debug)]
+pub struct NodeIter<'a, T, P: PointerKind = RcKind>(Option<&'a Node<T, P>>);
 
-impl<'a, T> PartialEq for NodeIter<'a, T> {
+impl<'a, T, P: PointerKind> Clone for NodeIter<'a, T, P> {
+    fn clone(&self) -> Self {
+        Self(self.0)
+    }
+}
+
+impl<'a, T, P: PointerKind> PartialEq for NodeIter<'a, T, P> {
     fn eq(&self, other: &Self) -> bool {
         match (self.0, other.0) {
             (Some(node1), Some(node2)) => node1 == node2,
@@ -31,7 +52,7 @@ impl<'a, T> PartialEq for NodeIter<'a, T> {
     }
 }
 
-impl<'a, T> Iterator for NodeIter<'a, T> {
+impl<'a, T, P: PointerKind> Iterator for NodeIter<'a, T, P> {
     type Item = &'a T;
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -47,10 +68,19 @@ impl<'a, T> Iterator for NodeIter<'a, T> {
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct NodeIterFinite<'a, T>(Option<&'a Node<T>>, Option<&'a Node<T>>);
+#[derive(Debug)]
+pub struct NodeIterFinite<'a, T, P: PointerKind = RcKind>(
+    Option<&'a Node<T, P>>,
+    Option<&'a Node<T, P>>,
+);
+
+impl<'a, T, P: PointerKind> Clone for NodeIterFinite<'a, T, P> {
+    fn clone(&self) -> Self {
+        Self(self.0, self.1)
+    }
+}
 
-impl<'a, T> PartialEq for NodeIterFinite<'a, T> {
+impl<'a, T, P: PointerKind> PartialEq for NodeIterFinite<'a, T, P> {
     fn eq(&self, other: &Self) -> bool {
         match (self.0, other.0) {
             (Some(node1), Some(node2)) => node1 == node2,
@@ -59,7 +89,7 @@ impl<'a, T> PartialEq for NodeIterFinite<'a, T> {
     }
 }
 
-impl<'a, T> Iterator for NodeIterFinite<'a, T> {
+impl<'a, T, P: PointerKind> Iterator for NodeIterFinite<'a, T, P> {
     type Item = &'a T;
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -106,18 +136,81 @@ impl<'a, T> Iterator for NodeIterFinite<'a, T> {
 /// // 24
 /// println!("{}", list_i32.evaluate(i32::mul, 1));
 /// ```
-#[derive(Debug)]
-pub struct MultiList<T> {
-    head: Ref<Node<T>>,
+///
+/// Nodes are linked with `Rc` by default, [`SyncMultiList`] links them with
+/// `Arc` instead.
+pub struct MultiList<T, P: PointerKind = RcKind> {
+    head: Ref<T, P>,
+}
+
+/// [`MultiList`], that can be sent to and shared between threads along with
+/// its branches
+///
+/// [`MultiList::new`] always makes an `Rc` list, so that its type is
+/// inferred, the `Arc` one is made with [`Default`].
+///
+/// ```
+/// use multi_list::list::SyncMultiList;
+///
+/// let mut list = SyncMultiList::default();
+/// list.push(1).push(2);
+///
+/// let mut branch = list.branch();
+/// let sum = std::thread::spawn(move || {
+///     branch.push(3);
+///     branch.evaluate(|sum, item| sum + item, 0)
+/// });
+/// assert_eq!(sum.join().unwrap(), 6);
+/// ```
+pub type SyncMultiList<T> = MultiList<T, ArcKind>;
+
+impl<T: Debug, P: PointerKind> Debug for MultiList<T, P> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        f.debug_struct("MultiList")
+            .field("head", &self.head.as_deref())
+            .finish()
+    }
+}
+
+impl<T, P: PointerKind> Default for MultiList<T, P> {
+    #[inline]
+    fn default() -> Self {
+        Self { head: None }
+    }
+}
+
+/// Error of [`MultiList::try_pop`], when the top node is shared with another
+/// branch, so the item can not be moved out of it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Shared;
+
+impl Display for Shared {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "node is shared with another branch")
+    }
+}
+
+impl std::error::Error for Shared {}
+
+/// Unlinks the nodes one by one, as dropping the nested pointers recursively
+/// overflows the stack on long lists, and stops at the first node, that is
+/// shared with another branch
+impl<T, P: PointerKind> Drop for MultiList<T, P> {
+    fn drop(&mut self) {
+        let mut rf = take(&mut self.head);
+        while let Some(node) = rf {
+            rf = P::into_inner(node).and_then(|mut node| take(&mut node.next));
+        }
+    }
 }
 
-impl<T: Display> Display for MultiList<T> {
+impl<T: Display, P: PointerKind> Display for MultiList<T, P> {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
         let mut rf = &self.head;
 
         write!(f, "[ head")?;
         while let Some(node) = rf {
-            write!(f, " -> [{}]{}", Rc::strong_count(node), node.item)?;
+            write!(f, " -> [{}]{}", P::strong_count(node), node.item)?;
             rf = &node.next;
         }
         write!(f, " ]")
@@ -125,68 +218,100 @@ impl<T: Display> Display for MultiList<T> {
 }
 
 impl<T> MultiList<T> {
+    /// Empty list with the default `Rc` links
     #[inline]
     pub fn new() -> Self {
-        Self { head: None }
+        Self::default()
+    }
+}
+
+impl<T, P: PointerKind> MultiList<T, P> {
+    /// Number of items, in `O(1)`
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.head.as_ref().map_or(0, |node| node.len)
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.head.is_none()
     }
 
+    /// The last pushed item
     #[inline]
-    pub fn iter(&self) -> NodeIter<T> {
-        NodeIter(match &self.head {
-            Some(node) => Some(&node),
-            None => None,
-        })
+    pub fn peek(&self) -> Option<&T> {
+        self.head.as_ref().map(|node| &node.item)
+    }
+
+    #[inline]
+    pub fn iter(&self) -> NodeIter<'_, T, P> {
+        NodeIter(self.head.as_deref())
     }
 
     #[inline]
     pub fn iter_to<'a>(
         &'a self,
-        node: NodeIter<'a, T>,
-    ) -> NodeIterFinite<'a, T> {
-        NodeIterFinite(
-            match &self.head {
-                Some(node) => Some(&node),
-                None => None,
-            },
-            node.0,
-        )
+        node: NodeIter<'a, T, P>,
+    ) -> NodeIterFinite<'a, T, P> {
+        NodeIterFinite(self.head.as_deref(), node.0)
     }
 
     #[inline]
     pub fn from_to<'a>(
-        first: NodeIter<'a, T>,
-        last: NodeIter<'a, T>,
-    ) -> NodeIterFinite<'a, T> {
+        first: NodeIter<'a, T, P>,
+        last: NodeIter<'a, T, P>,
+    ) -> NodeIterFinite<'a, T, P> {
         NodeIterFinite(first.0, last.0)
     }
 
     #[inline]
     pub fn branch(&self) -> Self {
         Self {
-            head: match &self.head {
-                Some(rf) => Some(rf.clone()),
-                None => None,
-            },
+            head: self.head.clone(),
+        }
+    }
+
+    /// Branch without the top item, in `O(1)`
+    #[inline]
+    pub fn tail(&self) -> Self {
+        Self {
+            head: self.head.as_ref().and_then(|node| node.next.clone()),
         }
     }
 
     pub fn push(&mut self, item: T) -> &mut Self {
-        self.head = Some(Rc::new(Node {
+        let next = take(&mut self.head);
+        self.head = Some(P::new(Node {
             item,
-            next: take(&mut self.head),
+            len: 1 + next.as_ref().map_or(0, |node| node.len),
+            next,
         }));
         self
     }
 
-    pub fn pop(&mut self) -> Option<T> {
-        let rf = take(&mut self.head)?;
-        let node = Rc::try_unwrap(rf).ok()?;
-
-        self.head = node.next;
-        Some(node.item)
+    /// Pops the top item, if its node is not shared with another branch,
+    /// and leaves the list unchanged otherwise
+    pub fn try_pop(&mut self) -> std::result::Result<Option<T>, Shared> {
+        let Some(rf) = take(&mut self.head) else {
+            return Ok(None);
+        };
+        match P::try_unwrap(rf) {
+            Ok(node) => {
+                self.head = node.next;
+                Ok(Some(node.item))
+            }
+            Err(rf) => {
+                self.head = Some(rf);
+                Err(Shared)
+            }
+        }
     }
 
-    pub fn evaluate<'a, U>(&'a self, fnc: fn(U, &'a T) -> U, mut def: U) -> U {
+    pub fn evaluate<'a, U, F: FnMut(U, &'a T) -> U>(
+        &'a self,
+        mut fnc: F,
+        mut def: U,
+    ) -> U {
         let mut rf = &self.head;
         while let Some(node) = rf {
             def = fnc(def, &node.item);
@@ -195,26 +320,33 @@ impl<T> MultiList<T> {
         def
     }
 
-    pub fn common<'a>(&'a self, other: &'a Self) -> NodeIter<'a, T> {
-        let mut rf0 = &self.head;
-        while let Some(node0) = rf0 {
-            if Rc::strong_count(node0) > 1 {
-                let mut rf1 = &other.head;
-                while let Some(node1) = rf1 {
-                    if Rc::ptr_eq(node0, node1) {
-                        return NodeIter(Some(node0.deref()));
-                    }
-                    rf1 = &node1.next;
-                }
+    /// First node, that is shared by both lists, in `O(n + m)`
+    ///
+    /// Nodes of the shared tail are at the same distance from the end in
+    /// both lists, so the longer list skips its extra nodes first.
+    pub fn common<'a>(&'a self, other: &'a Self) -> NodeIter<'a, T, P> {
+        let skip = |mut rf: &'a Ref<T, P>, n: usize| {
+            for _ in 0..n {
+                rf = &rf.as_ref().expect("list is long enough").next;
+            }
+            rf
+        };
+        let (len0, len1) = (self.len(), other.len());
+        let mut rf0 = skip(&self.head, len0.saturating_sub(len1));
+        let mut rf1 = skip(&other.head, len1.saturating_sub(len0));
+        while let (Some(node0), Some(node1)) = (rf0, rf1) {
+            if P::ptr_eq(node0, node1) {
+                return NodeIter(Some(node0.deref()));
             }
             rf0 = &node0.next;
+            rf1 = &node1.next;
         }
         NodeIter(None)
     }
 }
 
-impl<T: PartialEq> MultiList<T> {
-    pub fn find(&self, item: &T) -> NodeIter<T> {
+impl<T: PartialEq, P: PointerKind> MultiList<T, P> {
+    pub fn find(&self, item: &T) -> NodeIter<'_, T, P> {
         let mut rf = &self.head;
         while let Some(node) = rf {
             if node.item.eq(item) {
@@ -226,3 +358,271 @@ impl<T: PartialEq> MultiList<T> {
         NodeIter(None)
     }
 }
+
+impl<T, P: PointerKind> MultiList<T, P> {
+    /// List with the items from the top down, so the first one ends up on
+    /// the top, and iterating the list gives the items back in the same
+    /// order, unlike `collect`
+    pub fn from_top<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let items = iter.into_iter().collect::<Vec<_>>();
+        let mut list = Self::default();
+        list.extend(items.into_iter().rev());
+        list
+    }
+
+    /// New list with `f` applied to every item, in the same order
+    pub fn map<U, F: FnMut(&T) -> U>(&self, f: F) -> MultiList<U, P> {
+        MultiList::from_top(self.iter().map(f))
+    }
+}
+
+impl<T: Clone, P: PointerKind> MultiList<T, P> {
+    /// Pops the top item, cloning it out, if its node is shared with another
+    /// branch, see [`MultiList::try_pop`] to avoid cloning
+    pub fn pop(&mut self) -> Option<T> {
+        let rf = take(&mut self.head)?;
+        match P::try_unwrap(rf) {
+            Ok(node) => {
+                self.head = node.next;
+                Some(node.item)
+            }
+            Err(rf) => {
+                self.head = rf.next.clone();
+                Some(rf.item.clone())
+            }
+        }
+    }
+
+    /// New list with the items in the opposite order
+    pub fn reverse(&self) -> Self {
+        let mut list = Self::default();
+        list.extend(self.iter().cloned());
+        list
+    }
+
+    /// Pushes the items of `other` on top of this list, keeping their order,
+    /// so the top of `other` becomes the top of this list
+    ///
+    /// The items are cloned, unless this list is empty, then it shares the
+    /// nodes of `other`.
+    pub fn append(&mut self, other: &Self) -> &mut Self {
+        if self.is_empty() {
+            self.head = other.head.clone();
+        } else {
+            let items: Vec<_> = other.iter().collect();
+            self.extend(items.into_iter().rev().cloned());
+        }
+        self
+    }
+
+    /// New list with the items, for which `f` is `true`, in the same order
+    ///
+    /// The part of the list after the last removed item is shared with this
+    /// one, not copied.
+    pub fn filter<F: FnMut(&T) -> bool>(&self, mut f: F) -> Self {
+        let mut items = Vec::new();
+        let mut copied = 0;
+        let (mut tail, mut rf) = (&self.head, &self.head);
+        while let Some(node) = rf {
+            rf = &node.next;
+            let keep = f(&node.item);
+            items.push((keep, &node.item));
+            if !keep {
+                tail = rf;
+                copied = items.len();
+            }
+        }
+        let mut list = Self { head: tail.clone() };
+        let items = items[..copied].iter().rev();
+        list.extend(
+            items
+                .filter(|(keep, _)| *keep)
+                .map(|(_, item)| (*item).clone()),
+        );
+        list
+    }
+}
+
+impl<T, P: PointerKind> Clone for MultiList<T, P> {
+    /// Same as [`MultiList::branch`], in `O(1)`
+    #[inline]
+    fn clone(&self) -> Self {
+        self.branch()
+    }
+}
+
+impl<T: PartialEq, P: PointerKind> PartialEq for MultiList<T, P> {
+    /// Compares the items until the lists reach a shared node
+    fn eq(&self, other: &Self) -> bool {
+        if self.len() != other.len() {
+            return false;
+        }
+        let (mut rf0, mut rf1) = (&self.head, &other.head);
+        while let (Some(node0), Some(node1)) = (rf0, rf1) {
+            if P::ptr_eq(node0, node1) {
+                return true;
+            }
+            if node0.item != node1.item {
+                return false;
+            }
+            rf0 = &node0.next;
+            rf1 = &node1.next;
+        }
+        true
+    }
+}
+
+impl<T: Eq, P: PointerKind> Eq for MultiList<T, P> {}
+
+impl<T: Hash, P: PointerKind> Hash for MultiList<T, P> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(self.len());
+        for item in self {
+            item.hash(state);
+        }
+    }
+}
+
+impl<'a, T, P: PointerKind> IntoIterator for &'a MultiList<T, P> {
+    type Item = &'a T;
+    type IntoIter = NodeIter<'a, T, P>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Pushes the items in order, so the last one ends up on the top
+/// Pushes the items in order, so the last one ends up on the top
+impl<T, P: PointerKind> Extend<T> for MultiList<T, P> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for item in iter {
+            self.push(item);
+        }
+    }
+}
+
+/// Pushes the items in order, as [`Extend`] does, so the last one ends up on
+/// the top, see [`MultiList::from_top`] to keep the order instead
+impl<T, P: PointerKind> FromIterator<T> for MultiList<T, P> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut list = Self::default();
+        list.extend(iter);
+        list
+    }
+}
+
+/// Table of the nodes, that [`MultiList::push_interned`] reuses
+///
+/// Equal items pushed onto the same tail get the same node, so lists built
+/// separately through one interner share their equal suffixes, and
+/// [`MultiList::common`] finds them. The table keeps only weak pointers, the
+/// nodes are freed with the lists, see [`Interner::purge`].
+pub struct Interner<T, P: PointerKind = RcKind> {
+    nodes: HashMap<u64, Vec<Weak<T, P>>>,
+    state: RandomState,
+}
+
+impl<T, P: PointerKind> Default for Interner<T, P> {
+    fn default() -> Self {
+        Self {
+            nodes: HashMap::new(),
+            state: RandomState::new(),
+        }
+    }
+}
+
+impl<T> Interner<T> {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<T, P: PointerKind> Interner<T, P> {
+    /// Number of live nodes in the table
+    pub fn len(&self) -> usize {
+        let nodes = self.nodes.values().flatten();
+        nodes.filter(|node| P::upgrade(node).is_some()).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Forgets the nodes, that are already freed
+    pub fn purge(&mut self) {
+        self.nodes.retain(|_, nodes| {
+            nodes.retain(|node| P::upgrade(node).is_some());
+            !nodes.is_empty()
+        });
+    }
+}
+
+impl<T: Hash + Eq, P: PointerKind> Interner<T, P> {
+    /// Node with `item` on top of `next`, an existing one if possible
+    fn intern(&mut self, item: T, next: Ref<T, P>) -> P::Pointer<Node<T, P>> {
+        let tail = |next: &Ref<T, P>| {
+            next.as_deref().map(|node| node as *const Node<T, P>)
+        };
+        let nodes = self
+            .nodes
+            .entry(self.state.hash_one((&item, tail(&next))))
+            .or_default();
+        let mut found = None;
+        nodes.retain(|node| match P::upgrade(node) {
+            Some(node) => {
+                if found.is_none()
+                    && node.item == item
+                    && tail(&node.next) == tail(&next)
+                {
+                    found = Some(node);
+                } else {
+                    // the last pointer may be this one, if a list is dropped
+                    // in another thread
+                    drop(MultiList::<T, P> { head: Some(node) });
+                }
+                true
+            }
+            None => false,
+        });
+        if let Some(node) = found {
+            return node;
+        }
+        let node = P::new(Node {
+            len: 1 + next.as_ref().map_or(0, |node| node.len),
+            item,
+            next,
+        });
+        nodes.push(P::downgrade(&node));
+        node
+    }
+
+    /// List of the interned items in the same order, as by
+    /// [`MultiList::from_top`]
+    pub fn collect<I: IntoIterator<Item = T>>(
+        &mut self,
+        iter: I,
+    ) -> MultiList<T, P> {
+        let items = iter.into_iter().collect::<Vec<_>>();
+        let mut list = MultiList::default();
+        for item in items.into_iter().rev() {
+            list.push_interned(item, self);
+        }
+        list
+    }
+}
+
+impl<T: Hash + Eq, P: PointerKind> MultiList<T, P> {
+    /// Pushes `item`, reusing the node of `interner` with an equal item on
+    /// top of the same tail, if there is one
+    pub fn push_interned(
+        &mut self,
+        item: T,
+        interner: &mut Interner<T, P>,
+    ) -> &mut Self {
+        let next = take(&mut self.head);
+        self.head = Some(interner.intern(item, next));
+        self
+    }
+}
@@ -0,0 +1,121 @@
+use std::{
+    ops::Deref,
+    rc::{self, Rc},
+    sync::{self, Arc},
+};
+
+/// Reference counted pointer, that links the nodes of
+/// [`MultiList`](crate::list::MultiList) and is shared between branches
+pub trait PointerKind {
+    type Pointer<T>: Deref<Target = T> + Clone;
+
+    /// Pointer, that does not keep the value alive
+    type Weak<T>;
+
+    fn new<T>(value: T) -> Self::Pointer<T>;
+
+    fn downgrade<T>(this: &Self::Pointer<T>) -> Self::Weak<T>;
+
+    fn upgrade<T>(this: &Self::Weak<T>) -> Option<Self::Pointer<T>>;
+
+    /// Gives the value back, if this is the only pointer to it
+    fn try_unwrap<T>(this: Self::Pointer<T>) -> Result<T, Self::Pointer<T>>;
+
+    /// Gives the value back, if this is the only pointer to it, otherwise
+    /// drops the pointer. Out of several pointers, that are dropped at the
+    /// same time, exactly one gets the value.
+    fn into_inner<T>(this: Self::Pointer<T>) -> Option<T>;
+
+    fn strong_count<T>(this: &Self::Pointer<T>) -> usize;
+
+    fn ptr_eq<T>(this: &Self::Pointer<T>, other: &Self::Pointer<T>) -> bool;
+}
+
+/// Single threaded lists, linked with `Rc`
+#[derive(Debug, Clone, Copy)]
+pub enum RcKind {}
+
+impl PointerKind for RcKind {
+    type Pointer<T> = Rc<T>;
+    type Weak<T> = rc::Weak<T>;
+
+    #[inline]
+    fn new<T>(value: T) -> Rc<T> {
+        Rc::new(value)
+    }
+
+    #[inline]
+    fn downgrade<T>(this: &Rc<T>) -> rc::Weak<T> {
+        Rc::downgrade(this)
+    }
+
+    #[inline]
+    fn upgrade<T>(this: &rc::Weak<T>) -> Option<Rc<T>> {
+        this.upgrade()
+    }
+
+    #[inline]
+    fn try_unwrap<T>(this: Rc<T>) -> Result<T, Rc<T>> {
+        Rc::try_unwrap(this)
+    }
+
+    #[inline]
+    fn into_inner<T>(this: Rc<T>) -> Option<T> {
+        Rc::into_inner(this)
+    }
+
+    #[inline]
+    fn strong_count<T>(this: &Rc<T>) -> usize {
+        Rc::strong_count(this)
+    }
+
+    #[inline]
+    fn ptr_eq<T>(this: &Rc<T>, other: &Rc<T>) -> bool {
+        Rc::ptr_eq(this, other)
+    }
+}
+
+/// Lists, linked with `Arc`, which are `Send` and `Sync`, so branches can
+/// be handed to other threads
+#[derive(Debug, Clone, Copy)]
+pub enum ArcKind {}
+
+impl PointerKind for ArcKind {
+    type Pointer<T> = Arc<T>;
+    type Weak<T> = sync::Weak<T>;
+
+    #[inline]
+    fn new<T>(value: T) -> Arc<T> {
+        Arc::new(value)
+    }
+
+    #[inline]
+    fn downgrade<T>(this: &Arc<T>) -> sync::Weak<T> {
+        Arc::downgrade(this)
+    }
+
+    #[inline]
+    fn upgrade<T>(this: &sync::Weak<T>) -> Option<Arc<T>> {
+        this.upgrade()
+    }
+
+    #[inline]
+    fn try_unwrap<T>(this: Arc<T>) -> Result<T, Arc<T>> {
+        Arc::try_unwrap(this)
+    }
+
+    #[inline]
+    fn into_inner<T>(this: Arc<T>) -> Option<T> {
+        Arc::into_inner(this)
+    }
+
+    #[inline]
+    fn strong_count<T>(this: &Arc<T>) -> usize {
+        Arc::strong_count(this)
+    }
+
+    #[inline]
+    fn ptr_eq<T>(this: &Arc<T>, other: &Arc<T>) -> bool {
+        Arc::ptr_eq(this, other)
+    }
+}
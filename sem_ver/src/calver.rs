@@ -0,0 +1,228 @@
+use std::{cmp::Ordering, fmt, str::FromStr};
+
+use crate::version::{cmp_prerelease, parse_full, Version};
+
+fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 if year.is_multiple_of(4)
+            && (!year.is_multiple_of(100) || year.is_multiple_of(400)) =>
+        {
+            29
+        }
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Calendar version `YYYY.MM.DD`, e.g. `2024.02.29`
+///
+/// Components are validated as a calendar date, bumping the day moves to
+/// the next date, so `2024.02.29` is followed by `2024.03.01`. Month and
+/// day are zero-padded, unpadded ones are accepted as well.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CalVer {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub prerelease: Option<Box<str>>,
+}
+
+impl Version for CalVer {
+    const LEVELS: &'static [&'static str] = &["year", "month", "day"];
+
+    fn from_parts(parts: &[&str], prerelease: Option<&str>) -> Option<Self> {
+        let year = parts.first()?.parse().ok()?;
+        let month = parts.get(1).map_or(Some(1), |m| m.parse().ok())?;
+        let day = parts.get(2).map_or(Some(1), |d| d.parse().ok())?;
+        if !(1..=12).contains(&month)
+            || !(1..=days_in_month(year, month)).contains(&day)
+        {
+            return None;
+        }
+        Some(CalVer {
+            year,
+            month,
+            day,
+            prerelease: prerelease.map(Box::from),
+        })
+    }
+
+    fn fmt_parts(
+        &self,
+        precision: usize,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        match precision {
+            0 => Ok(()),
+            1 => write!(f, "{:04}", self.year),
+            2 => write!(f, "{:04}.{:02}", self.year, self.month),
+            _ => {
+                write!(f, "{:04}.{:02}.{:02}", self.year, self.month, self.day)
+            }
+        }
+    }
+
+    fn is_lowest(&self, level: usize) -> bool {
+        match level {
+            0 => self.year == 0,
+            1 => self.month == 1,
+            _ => self.day == 1,
+        }
+    }
+
+    fn bump(&self, level: usize) -> Option<Self> {
+        let (mut year, mut month, mut day) = (self.year, self.month, self.day);
+        match level {
+            0 => (year, month, day) = (year.checked_add(1)?, 1, 1),
+            1 => (month, day) = (month + 1, 1),
+            _ => day += 1,
+        }
+        if day > days_in_month(year, month) {
+            (month, day) = (month + 1, 1);
+        }
+        if month > 12 {
+            (year, month) = (year.checked_add(1)?, 1);
+        }
+        Some(CalVer {
+            year,
+            month,
+            day,
+            prerelease: None,
+        })
+    }
+
+    fn prerelease(&self) -> Option<&str> {
+        self.prerelease.as_deref()
+    }
+
+    fn with_prerelease(&self, prerelease: Option<&str>) -> Self {
+        CalVer {
+            prerelease: prerelease.map(Box::from),
+            ..*self
+        }
+    }
+}
+
+/// Calendar version `YY.0M.MICRO`, e.g. `24.02.3`
+///
+/// `YY` is the number of years since 2000, `0M` is zero-padded month and
+/// `MICRO` counts releases within the month.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShortCalVer {
+    pub year: u16,
+    pub month: u8,
+    pub micro: u32,
+    pub prerelease: Option<Box<str>>,
+}
+
+impl Version for ShortCalVer {
+    const LEVELS: &'static [&'static str] = &["year", "month", "micro"];
+
+    fn from_parts(parts: &[&str], prerelease: Option<&str>) -> Option<Self> {
+        let year = parts.first()?.parse().ok()?;
+        let month = parts.get(1).map_or(Some(1), |m| m.parse().ok())?;
+        let micro = parts.get(2).map_or(Some(0), |m| m.parse().ok())?;
+        if !(1..=12).contains(&month) {
+            return None;
+        }
+        Some(ShortCalVer {
+            year,
+            month,
+            micro,
+            prerelease: prerelease.map(Box::from),
+        })
+    }
+
+    fn fmt_parts(
+        &self,
+        precision: usize,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        match precision {
+            0 => Ok(()),
+            1 => write!(f, "{}", self.year),
+            2 => write!(f, "{}.{:02}", self.year, self.month),
+            _ => write!(f, "{}.{:02}.{}", self.year, self.month, self.micro),
+        }
+    }
+
+    fn is_lowest(&self, level: usize) -> bool {
+        match level {
+            0 => self.year == 0,
+            1 => self.month == 1,
+            _ => self.micro == 0,
+        }
+    }
+
+    fn bump(&self, level: usize) -> Option<Self> {
+        let (mut year, mut month, mut micro) =
+            (self.year, self.month, self.micro);
+        match level {
+            0 => (year, month, micro) = (year.checked_add(1)?, 1, 0),
+            1 => (month, micro) = (month + 1, 0),
+            _ => micro = micro.checked_add(1)?,
+        }
+        if month > 12 {
+            (year, month) = (year.checked_add(1)?, 1);
+        }
+        Some(ShortCalVer {
+            year,
+            month,
+            micro,
+            prerelease: None,
+        })
+    }
+
+    fn prerelease(&self) -> Option<&str> {
+        self.prerelease.as_deref()
+    }
+
+    fn with_prerelease(&self, prerelease: Option<&str>) -> Self {
+        ShortCalVer {
+            prerelease: prerelease.map(Box::from),
+            ..*self
+        }
+    }
+}
+
+macro_rules! calver_impl {
+    ($Name:ident, $a:ident, $b:ident, $c:ident, $what:literal) => {
+        impl Ord for $Name {
+            fn cmp(&self, other: &Self) -> Ordering {
+                (self.$a, self.$b, self.$c)
+                    .cmp(&(other.$a, other.$b, other.$c))
+                    .then_with(|| {
+                        cmp_prerelease(self.prerelease(), other.prerelease())
+                    })
+            }
+        }
+
+        impl PartialOrd for $Name {
+            fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+
+        impl fmt::Display for $Name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                self.fmt_parts(Self::LEVELS.len(), f)?;
+                if let Some(prerelease) = &self.prerelease {
+                    write!(f, "-{}", prerelease)?;
+                }
+                Ok(())
+            }
+        }
+
+        impl FromStr for $Name {
+            type Err = String;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                parse_full(s, true).ok_or_else(|| String::from($what))
+            }
+        }
+    };
+}
+
+calver_impl! { CalVer, year, month, day, "not a YYYY.MM.DD calver" }
+calver_impl! { ShortCalVer, year, month, micro, "not a YY.0M.MICRO calver" }
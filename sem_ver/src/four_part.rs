@@ -0,0 +1,99 @@
+use std::{cmp::Ordering, fmt, str::FromStr};
+
+use crate::version::{cmp_prerelease, parse_full, Version};
+
+/// Four-component version `major.minor.build.revision`, e.g. `10.0.19041.1`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FourPart {
+    pub parts: [u32; 4],
+    pub prerelease: Option<Box<str>>,
+}
+
+impl Version for FourPart {
+    const LEVELS: &'static [&'static str] =
+        &["major", "minor", "build", "revision"];
+
+    fn from_parts(parts: &[&str], prerelease: Option<&str>) -> Option<Self> {
+        let mut numbers = [0; 4];
+        for (number, part) in numbers.iter_mut().zip(parts) {
+            *number = part.parse().ok()?;
+        }
+        Some(FourPart {
+            parts: numbers,
+            prerelease: prerelease.map(Box::from),
+        })
+    }
+
+    fn fmt_parts(
+        &self,
+        precision: usize,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        for (i, part) in self.parts.iter().take(precision).enumerate() {
+            if i > 0 {
+                f.write_str(".")?;
+            }
+            write!(f, "{part}")?;
+        }
+        Ok(())
+    }
+
+    fn is_lowest(&self, level: usize) -> bool {
+        self.parts[level.min(3)] == 0
+    }
+
+    fn bump(&self, level: usize) -> Option<Self> {
+        let level = level.min(3);
+        let mut parts = self.parts;
+        parts[level] = parts[level].checked_add(1)?;
+        parts[level + 1..].fill(0);
+        Some(FourPart {
+            parts,
+            prerelease: None,
+        })
+    }
+
+    fn prerelease(&self) -> Option<&str> {
+        self.prerelease.as_deref()
+    }
+
+    fn with_prerelease(&self, prerelease: Option<&str>) -> Self {
+        FourPart {
+            parts: self.parts,
+            prerelease: prerelease.map(Box::from),
+        }
+    }
+}
+
+impl Ord for FourPart {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.parts
+            .cmp(&other.parts)
+            .then_with(|| cmp_prerelease(self.prerelease(), other.prerelease()))
+    }
+}
+
+impl PartialOrd for FourPart {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for FourPart {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_parts(Self::LEVELS.len(), f)?;
+        if let Some(prerelease) = &self.prerelease {
+            write!(f, "-{}", prerelease)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for FourPart {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_full(s, false)
+            .ok_or_else(|| String::from("not a a.b.c.d version"))
+    }
+}
@@ -1,161 +1,559 @@
-use std::{cmp::*, fmt, str::FromStr};
-
-use num_integer::Integer;
-use regex::Regex;
-
-#[derive(Clone, Debug)]
-struct SemVer<Major: Integer = u16, Minor: Integer = u16, Patch: Integer = u32>
-{
-    major: Major,
-    minor: Minor,
-    patch: Patch,
-    prerelease: Option<Box<str>>,
-    buildmetadata: Option<Box<str>>,
-}
+use std::{
+    env::args,
+    fmt::Display,
+    fs::read_to_string,
+    io::{stdin, stdout, BufRead, ErrorKind, Write},
+    process::ExitCode,
+};
+
+use calver::{CalVer, ShortCalVer};
+use four_part::FourPart;
+use req::VersionReq;
+use resolve::Registry;
+use semver::SemVer;
+use version::Version;
+
+mod calver;
+mod four_part;
+mod req;
+mod resolve;
+mod semver;
+mod version;
+
+static USAGE: &str = "\
+Usage: sem_ver [--scheme <SCHEME>] <COMMAND>
+
+Commands:
+    sem_ver sort [--reverse]     sort versions from stdin
+    sem_ver filter <REQ>         print versions from stdin, that match <REQ>
+    sem_ver max <REQ>            print greatest version from stdin, that matches <REQ>
+    sem_ver bump <LEVEL> <VER>   bump <VER> by <LEVEL> component or pre
+    sem_ver resolve <FILE> <DEPS>
+                                 pick versions of packages from manifest <FILE>,
+                                 that satisfy `;`-separated dependencies <DEPS>
+
+Schemes:
+    semver        major.minor.patch (default)
+    calver        YYYY.MM.DD
+    calver-short  YY.0M.MICRO
+    four-part     major.minor.build.revision
+
+Versions on stdin are separated by whitespace,
+requirements are comma-separated comparators like `>=1.2, <1.5` or `^0.3`";
 
-impl<Major, Minor, Patch> fmt::Display for SemVer<Major, Minor, Patch>
-where
-    Major: Integer + fmt::Display,
-    Minor: Integer + fmt::Display,
-    Patch: Integer + fmt::Display,
-{
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
-        if let Some(prerelease) = &self.prerelease {
-            write!(f, "-{}", &*prerelease)?;
+/// Reads all whitespace-separated versions from `input`
+fn read_versions<V: Version>(input: impl BufRead) -> Result<Vec<V>, String> {
+    let mut versions = Vec::new();
+    for (line_num, line) in input.lines().enumerate() {
+        let line = line.map_err(|e| e.to_string())?;
+        for word in line.split_whitespace() {
+            let version = word
+                .parse()
+                .map_err(|e| format!("line {}: `{word}`: {e}", line_num + 1))?;
+            versions.push(version);
         }
-        if let Some(buildmetadata) = &self.buildmetadata {
-            write!(f, "+{}", &*buildmetadata)?;
+    }
+    Ok(versions)
+}
+
+/// Prints `lines` to `out`, stopping quietly if the reader went away
+fn print_all<T: Display>(
+    mut out: impl Write,
+    lines: impl IntoIterator<Item = T>,
+) -> Result<(), String> {
+    for line in lines {
+        match writeln!(out, "{line}") {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::BrokenPipe => break,
+            Err(e) => return Err(e.to_string()),
         }
-        Ok(())
     }
+    Ok(())
 }
 
-impl<Major, Minor, Patch> Eq for SemVer<Major, Minor, Patch>
-where
-    Major: Integer,
-    Minor: Integer,
-    Patch: Integer,
-{
+fn parse_req<V: Version>(
+    req: Option<&String>,
+) -> Result<VersionReq<V>, String> {
+    let req = req.ok_or("Specify version requirement")?;
+    req.parse().map_err(|e| format!("`{req}`: {e}"))
 }
 
-impl<Major, Minor, Patch> PartialEq for SemVer<Major, Minor, Patch>
-where
-    Major: Integer,
-    Minor: Integer,
-    Patch: Integer,
-{
-    fn eq(&self, other: &Self) -> bool {
-        self.cmp(other).is_eq()
+fn run<V: Version>(
+    options: &[String],
+    input: impl BufRead,
+    out: impl Write,
+) -> Result<ExitCode, String> {
+    match options.first().map(String::as_str) {
+        Some("sort") => {
+            let mut versions = read_versions::<V>(input)?;
+            // stable in both directions, so versions of the same precedence,
+            // like `1.0.0` and `1.0.0+build`, keep their input order
+            if options.get(1).map(String::as_str) == Some("--reverse") {
+                versions.sort_by(|a, b| b.cmp(a));
+            } else {
+                versions.sort();
+            }
+            print_all(out, versions)?;
+        }
+        Some("filter") => {
+            let req = parse_req::<V>(options.get(1))?;
+            let versions = read_versions(input)?;
+            print_all(out, versions.iter().filter(|v| req.matches(v)))?;
+        }
+        Some("max") => {
+            let req = parse_req::<V>(options.get(1))?;
+            let versions = read_versions(input)?;
+            match req.max_matching(&versions) {
+                Some(version) => print_all(out, [version])?,
+                None => {
+                    eprintln!("No version matches `{req}`");
+                    return Ok(ExitCode::FAILURE);
+                }
+            }
+        }
+        Some("bump") => {
+            let level = options.get(1).ok_or("Specify level to bump")?;
+            let version = options.get(2).ok_or("Specify version to bump")?;
+            let version = version
+                .parse::<V>()
+                .map_err(|e| format!("`{version}`: {e}"))?;
+            print_all(out, [version.bump_named(level)?])?;
+        }
+        Some("resolve") => {
+            let path = options.get(1).ok_or("Specify manifest file")?;
+            let registry = read_to_string(path)
+                .map_err(|e| format!("{path}: {e}"))?
+                .parse::<Registry<V>>()
+                .map_err(|e| format!("{path}: {e}"))?;
+            let root = resolve::parse_dependencies(
+                options.get(2).ok_or("Specify dependencies")?,
+            )?;
+            match registry.resolve(&root) {
+                Ok(resolution) => print_all(
+                    out,
+                    resolution
+                        .iter()
+                        .map(|(name, version)| format!("{name} {version}")),
+                )?,
+                Err(conflict) => {
+                    eprint!("No solution:\n{conflict}");
+                    return Ok(ExitCode::FAILURE);
+                }
+            }
+        }
+        _ => return Err(String::from(USAGE)),
     }
+    Ok(ExitCode::SUCCESS)
 }
 
-impl<Major, Minor, Patch> Ord for SemVer<Major, Minor, Patch>
-where
-    Major: Integer,
-    Minor: Integer,
-    Patch: Integer,
-{
-    fn cmp(&self, other: &Self) -> Ordering {
-        fn cmp_meta(
-            first: &Option<Box<str>>,
-            second: &Option<Box<str>>,
-        ) -> Ordering {
-            match (first.as_ref(), second.as_ref()) {
-                (None, None) => Ordering::Equal,
-                (None, Some(_)) => Ordering::Greater,
-                (Some(_), None) => Ordering::Less,
-                (Some(first), Some(second)) => first.cmp(&*second),
-            }
+fn main() -> ExitCode {
+    let mut options = args().skip(1).collect::<Vec<_>>();
+
+    let scheme = match options.first().map(String::as_str) {
+        Some("--scheme") => {
+            let scheme = options.get(1).cloned().unwrap_or_default();
+            options.drain(..2.min(options.len()));
+            scheme
         }
+        _ => String::from("semver"),
+    };
+    let (input, out) = (stdin().lock(), stdout().lock());
+    let result = match scheme.as_str() {
+        "semver" => run::<SemVer>(&options, input, out),
+        "calver" => run::<CalVer>(&options, input, out),
+        "calver-short" => run::<ShortCalVer>(&options, input, out),
+        "four-part" => run::<FourPart>(&options, input, out),
+        _ => Err(format!("Unknown scheme `{scheme}`\n\n{USAGE}")),
+    };
 
-        self.major
-            .cmp(&other.major)
-            .then_with(|| self.minor.cmp(&other.minor))
-            .then_with(|| self.patch.cmp(&other.patch))
-            .then_with(|| cmp_meta(&self.prerelease, &other.prerelease))
-            .then_with(|| cmp_meta(&self.buildmetadata, &other.buildmetadata))
+    match result {
+        Ok(code) => code,
+        Err(s) => {
+            eprintln!("{}", s);
+            ExitCode::FAILURE
+        }
     }
 }
 
-impl<Major, Minor, Patch> PartialOrd for SemVer<Major, Minor, Patch>
-where
-    Major: Integer,
-    Minor: Integer,
-    Patch: Integer,
-{
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
+#[cfg(test)]
+mod resolve_test {
+    use crate::resolve::{parse_dependencies, Conflict, Registry, Resolution};
+
+    fn resolve(manifest: &str, root: &str) -> Result<Resolution, Conflict> {
+        let registry = manifest.parse::<Registry>().unwrap();
+        registry.resolve(&parse_dependencies(root).unwrap())
+    }
+
+    fn versions(resolution: &Resolution) -> Vec<String> {
+        resolution
+            .iter()
+            .map(|(name, version)| format!("{name} {version}"))
+            .collect()
     }
-}
 
-#[derive(Clone, Debug)]
-enum SemVerParseError {
-    NotASemVer,
-    NotSingleSemVer,
+    #[test]
+    fn greatest_versions() {
+        let manifest = "
+            app 1.0.0: log ^0.4; serde >=1.0, <1.5
+            log 0.4.17
+            log 0.4.20
+            log 0.5.0
+            serde 1.4.9
+            serde 1.5.0
+        ";
+        let resolution = resolve(manifest, "app").unwrap();
+        assert_eq!(
+            versions(&resolution),
+            ["app 1.0.0", "log 0.4.20", "serde 1.4.9"]
+        );
+    }
+
+    #[test]
+    fn backtracking() {
+        // the latest `a` needs `c` 2, which conflicts with `b`
+        let manifest = "
+            a 1.0.0: c ^1
+            a 1.1.0: c ^2
+            b 1.0.0: c ^1.2
+            c 1.2.0
+            c 1.3.0
+            c 2.0.0
+        ";
+        let resolution = resolve(manifest, "a; b").unwrap();
+        assert_eq!(versions(&resolution), ["a 1.0.0", "b 1.0.0", "c 1.3.0"]);
+    }
+
+    #[test]
+    fn no_solution() {
+        let manifest = "
+            a 1.0.0: c ^2
+            a 1.1.0: c ^2.1
+            b 1.0.0: c ^1
+            c 1.0.0
+            c 2.1.0
+        ";
+        let conflict = resolve(manifest, "a; b").unwrap_err();
+        assert_eq!(
+            conflict.to_string(),
+            "\
+every version of `a` leads to a conflict:
+  root requires a *
+  a 1.1.0:
+    every version of `b` leads to a conflict:
+      root requires b *
+      b 1.0.0:
+        no version of `c` satisfies:
+          a 1.1.0 requires c ^2.1
+          b 1.0.0 requires c ^1
+          available: 1.0.0, 2.1.0
+  a 1.0.0:
+    every version of `b` leads to a conflict:
+      root requires b *
+      b 1.0.0:
+        no version of `c` satisfies:
+          a 1.0.0 requires c ^2
+          b 1.0.0 requires c ^1
+          available: 1.0.0, 2.1.0
+"
+        );
+    }
+
+    #[test]
+    fn unknown_package() {
+        let manifest = "
+            a 1.0.0
+            a 2.0.0
+        ";
+        // `b` is missing regardless of `a`, so the other version is not tried
+        let conflict = resolve(manifest, "a; b ^1").unwrap_err();
+        assert_eq!(
+            conflict.to_string(),
+            "\
+no versions of `b` are known:
+  root requires b ^1
+"
+        );
+    }
 }
 
-impl<'t, Major, Minor, Patch> FromStr for SemVer<Major, Minor, Patch>
-where
-    Major: Integer,
-    Minor: Integer,
-    Patch: Integer,
-{
-    type Err = SemVerParseError;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let regex = Regex::new(concat!(
-            r"^[vV]?",
-            r"(?P<major>0|[1-9]\d*)",
-            r"\.(?P<minor>0|[1-9]\d*)",
-            r"\.(?P<patch>0|[1-9]\d*)",
-            r"(?:-(?P<prerelease>(?:0|[1-9]\d*|\d*[a-zA-Z-][0-9a-zA-Z-]*)(?:\.(?:0|[1-9]\d*|\d*[a-zA-Z-][0-9a-zA-Z-]*))*))?",
-            r"(?:\+(?P<buildmetadata>[0-9a-zA-Z-]+(?:\.[0-9a-zA-Z-]+)*))?",
-            r"$",
-        )).unwrap();
-        let mut captures = regex.captures_iter(s).into_iter();
-
-        match (captures.next(), captures.next()) {
-            (Some(capture), None) => unsafe {
-                // SAFETY: obligatory regex groups
-                let major = capture.name("major").unwrap_unchecked().as_str();
-                let minor = capture.name("minor").unwrap_unchecked().as_str();
-                let patch = capture.name("patch").unwrap_unchecked().as_str();
-                Ok(SemVer {
-                    // SAFETY: regex contains only decimal digits
-                    major: Major::from_str_radix(major, 10).unwrap_unchecked(),
-                    minor: Minor::from_str_radix(minor, 10).unwrap_unchecked(),
-                    patch: Patch::from_str_radix(patch, 10).unwrap_unchecked(),
-                    prerelease: capture
-                        .name("prerelease")
-                        .map(|m| Box::from(m.as_str())),
-                    buildmetadata: capture
-                        .name("buildmetadata")
-                        .map(|m| Box::from(m.as_str())),
-                })
-            },
-            (Some(_), Some(_)) => Err(SemVerParseError::NotSingleSemVer),
-            _ => Err(SemVerParseError::NotASemVer),
+#[cfg(test)]
+mod req_test {
+    use std::ops::Bound::{self, *};
+
+    use crate::{
+        calver::{CalVer, ShortCalVer},
+        four_part::FourPart,
+        req::{Comparator, VersionReq},
+        semver::SemVer,
+        version::Version,
+    };
+
+    fn bounds(comparator: &str) -> (Bound<String>, Bound<String>) {
+        let (lower, upper) = comparator.parse::<Comparator>().unwrap().bounds();
+        (lower.map(|v| v.to_string()), upper.map(|v| v.to_string()))
+    }
+
+    fn included(v: &str) -> Bound<String> {
+        Included(v.to_string())
+    }
+
+    fn excluded(v: &str) -> Bound<String> {
+        Excluded(v.to_string())
+    }
+
+    fn matching<V: Version>(req: &str, versions: &[&str]) -> Vec<String> {
+        let req = req.parse::<VersionReq<V>>().unwrap();
+        versions
+            .iter()
+            .map(|v| v.parse::<V>().ok().unwrap())
+            .filter(|v| req.matches(v))
+            .map(|v| v.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn semver() {
+        let versions = ["0.2.9", "0.3.0", "0.3.7", "0.4.0", "1.0.0-rc.1"];
+        assert_eq!(matching::<SemVer>("^0.3", &versions), ["0.3.0", "0.3.7"]);
+        assert_eq!(matching::<SemVer>("~0", &versions)[..], versions[..4]);
+        assert_eq!(matching::<SemVer>(">0.3, <=1", &versions), ["0.4.0"]);
+        assert_eq!(
+            matching::<SemVer>(">=1.0.0-rc.0", &versions),
+            ["1.0.0-rc.1"]
+        );
+    }
+
+    #[test]
+    fn comparator_bounds() {
+        assert_eq!(bounds("*"), (Unbounded, Unbounded));
+        assert_eq!(bounds("=1.2.3"), (included("1.2.3"), included("1.2.3")));
+        assert_eq!(bounds("=1.2"), (included("1.2.0"), excluded("1.3.0")));
+        assert_eq!(bounds("1.*"), (included("1.0.0"), excluded("2.0.0")));
+        assert_eq!(bounds(">1.2.3"), (excluded("1.2.3"), Unbounded));
+        assert_eq!(bounds(">1.2"), (included("1.3.0"), Unbounded));
+        assert_eq!(bounds(">=1.2"), (included("1.2.0"), Unbounded));
+        assert_eq!(bounds("<1.2"), (Unbounded, excluded("1.2.0")));
+        assert_eq!(bounds("<=1.2.3"), (Unbounded, included("1.2.3")));
+        assert_eq!(bounds("<=1.2"), (Unbounded, excluded("1.3.0")));
+        assert_eq!(bounds("~1.2.3"), (included("1.2.3"), excluded("1.3.0")));
+        assert_eq!(bounds("~1"), (included("1.0.0"), excluded("2.0.0")));
+        assert_eq!(bounds("^1.2"), (included("1.2.0"), excluded("2.0.0")));
+        assert_eq!(bounds("^0.3"), (included("0.3.0"), excluded("0.4.0")));
+        assert_eq!(bounds("^0.0.3"), (included("0.0.3"), excluded("0.0.4")));
+    }
+
+    #[test]
+    fn greatest_bounds() {
+        assert_eq!(bounds("^65535"), (included("65535.0.0"), Unbounded));
+        assert_eq!(bounds("=65535.x"), (included("65535.0.0"), Unbounded));
+        assert_eq!(bounds("<=65535"), (Unbounded, Unbounded));
+        assert_eq!(bounds("~1.65535"), (included("1.65535.0"), Unbounded));
+
+        let versions = ["65534.0.0", "65535.0.0", "65535.65535.4294967295"];
+        assert_eq!(matching::<SemVer>(">65535", &versions), [] as [&str; 0]);
+        assert_eq!(matching::<SemVer>("^65535", &versions)[..], versions[1..]);
+        assert_eq!(matching::<SemVer>("<=65535", &versions), versions);
+    }
+
+    #[test]
+    fn prerelease_opt_in() {
+        let versions = ["1.0.0", "1.1.0-alpha", "1.1.0", "1.2.0-rc.1"];
+        assert_eq!(matching::<SemVer>("^1.0.0", &versions), ["1.0.0", "1.1.0"]);
+        assert_eq!(
+            matching::<SemVer>(">=1.1.0-alpha", &versions),
+            ["1.1.0-alpha", "1.1.0"]
+        );
+        assert_eq!(
+            matching::<SemVer>(">=1.1.0-alpha, <2", &versions),
+            ["1.1.0-alpha", "1.1.0"]
+        );
+        assert_eq!(
+            matching::<SemVer>("=1.2.0-rc.1", &versions),
+            ["1.2.0-rc.1"]
+        );
+        assert_eq!(matching::<SemVer>("*", &versions), ["1.0.0", "1.1.0"]);
+    }
+
+    #[test]
+    fn calver() {
+        let versions = ["2023.12.31", "2024.01.01", "2024.02.29", "2025.01.01"];
+        assert_eq!(
+            matching::<CalVer>("2024.*", &versions),
+            ["2024.01.01", "2024.02.29"]
+        );
+        assert_eq!(matching::<CalVer>("<2024.2", &versions)[..], versions[..2]);
+        assert_eq!(
+            matching::<CalVer>("=2024.1.1", &["2024.1.1"]),
+            ["2024.01.01"]
+        );
+        assert!("2023.2.29".parse::<CalVer>().is_err());
+
+        let version = "2024.02.29".parse::<CalVer>().unwrap();
+        assert_eq!(
+            version.bump_named("day").unwrap().to_string(),
+            "2024.03.01"
+        );
+        assert_eq!(
+            version.bump_named("month").unwrap().to_string(),
+            "2024.03.01"
+        );
+        let version = "2024.01.31".parse::<CalVer>().unwrap();
+        assert_eq!(
+            version.bump_named("month").unwrap().to_string(),
+            "2024.02.01"
+        );
+        for text in ["2024.01.05", "0999.10.01-rc.1"] {
+            assert_eq!(text.parse::<CalVer>().unwrap().to_string(), text);
         }
+
+        let version = "65535.12.31".parse::<CalVer>().unwrap();
+        assert!(version.bump_named("year").is_err());
+        assert!(version.bump_named("day").is_err());
+        assert_eq!(matching::<CalVer>(">=65535.12", &["65535.12.31"]).len(), 1);
+    }
+
+    #[test]
+    fn short_calver() {
+        let versions = ["23.12.4", "24.01.0", "24.01.3", "24.02.0"];
+        assert_eq!(
+            matching::<ShortCalVer>("~24.1", &versions),
+            ["24.01.0", "24.01.3"]
+        );
+
+        let version = "23.12.4".parse::<ShortCalVer>().unwrap();
+        assert_eq!(version.bump_named("month").unwrap().to_string(), "24.01.0");
+
+        let version = "65535.12.4294967295".parse::<ShortCalVer>().unwrap();
+        assert!(version.bump_named("year").is_err());
+        assert!(version.bump_named("month").is_err());
+        assert!(version.bump_named("micro").is_err());
+        assert_eq!(
+            matching::<ShortCalVer>("^65535", &["65535.12.4294967295"]),
+            ["65535.12.4294967295"]
+        );
+    }
+
+    #[test]
+    fn four_part() {
+        let versions = ["1.2.3.4", "1.2.3.5", "1.2.4.0", "1.3.0.0"];
+        assert_eq!(
+            matching::<FourPart>("=1.2.3", &versions),
+            ["1.2.3.4", "1.2.3.5"]
+        );
+        assert_eq!(matching::<FourPart>("^1.2.3.4", &versions), versions);
+        assert!("1.2.3".parse::<FourPart>().is_err());
+        assert!("1.2.3.4.5".parse::<VersionReq<FourPart>>().is_err());
+
+        let version = "4294967295.0.0.4294967295".parse::<FourPart>().unwrap();
+        assert!(version.bump_named("major").is_err());
+        assert!(version.bump_named("revision").is_err());
+        assert!(version.bump_named("pre").is_err());
+        assert_eq!(
+            version.bump_named("build").unwrap().to_string(),
+            "4294967295.0.1.0"
+        );
+        assert_eq!(
+            matching::<FourPart>("=4294967295", &["4294967295.0.0.0"]),
+            ["4294967295.0.0.0"]
+        );
+    }
+}
+
+#[cfg(test)]
+mod bump_test {
+    use crate::{run, semver::SemVer, version::Version};
+
+    fn bump(level: &str, version: &str) -> Result<String, String> {
+        let version = version.parse::<SemVer>().unwrap();
+        version.bump_named(level).map(|v| v.to_string())
+    }
+
+    #[test]
+    fn semver() {
+        assert_eq!(bump("major", "1.2.3-rc.1").unwrap(), "2.0.0");
+        assert_eq!(bump("minor", "1.2.3").unwrap(), "1.3.0");
+        assert_eq!(bump("patch", "1.2.3").unwrap(), "1.2.4");
+        assert_eq!(bump("pre", "1.2.3").unwrap(), "1.2.4-0");
+        assert_eq!(bump("pre", "1.2.3-rc.1").unwrap(), "1.2.3-rc.2");
+        assert!(bump("build", "1.2.3").is_err());
+    }
+
+    #[test]
+    fn overflow() {
+        assert!(bump("major", "65535.0.0").is_err());
+        assert!(bump("minor", "1.65535.0").is_err());
+        assert!(bump("patch", "1.2.4294967295").is_err());
+        assert!(bump("pre", "1.2.4294967295").is_err());
+        assert_eq!(bump("minor", "65535.0.0").unwrap(), "65535.1.0");
+
+        assert!(bump("pre", "1.0.0-rc.18446744073709551615").is_err());
+        assert!(bump("pre", "1.0.0-18446744073709551615").is_err());
+
+        let options = ["bump", "major", "65535.0.0"].map(String::from);
+        assert_eq!(
+            run::<SemVer>(&options, &[][..], Vec::new()).unwrap_err(),
+            "`65535.0.0` overflows when bumping major"
+        );
     }
 }
 
-fn main() {
-    let sem_ver = vec!["0.0.1", "2.2.0", "v3.0.0-rc", "3.0.0"];
-    let sem_ver = sem_ver
-        .into_iter()
-        .map(|v| v.parse::<SemVer>())
-        .collect::<Result<Vec<_>, _>>()
-        .unwrap();
+#[cfg(test)]
+mod cli_test {
+    use std::process::ExitCode;
+
+    use crate::{run, semver::SemVer};
+
+    /// Output of the command with `input` on stdin
+    fn cli(options: &[&str], input: &str) -> (ExitCode, String) {
+        let options = options.iter().map(|o| o.to_string()).collect::<Vec<_>>();
+        let mut out = Vec::new();
+        let code = run::<SemVer>(&options, input.as_bytes(), &mut out).unwrap();
+        (code, String::from_utf8(out).unwrap())
+    }
+
+    fn success(options: &[&str], input: &str) -> String {
+        let (code, out) = cli(options, input);
+        assert_eq!(code, ExitCode::SUCCESS);
+        out
+    }
+
+    #[test]
+    fn sort() {
+        let input = "1.0.0 0.9.0+b 1.0.0-rc.1\n0.9.0 0.10.0";
+        assert_eq!(
+            success(&["sort"], input),
+            "0.9.0+b\n0.9.0\n0.10.0\n1.0.0-rc.1\n1.0.0\n"
+        );
+        assert_eq!(
+            success(&["sort", "--reverse"], input),
+            "1.0.0\n1.0.0-rc.1\n0.10.0\n0.9.0+b\n0.9.0\n"
+        );
+        assert_eq!(success(&["sort"], "0.9.0 0.9.0+b"), "0.9.0\n0.9.0+b\n");
 
-    let (start, end) = (sem_ver[1].clone(), sem_ver[3].clone());
-    let range = start..=end;
+        let options = ["sort"].map(String::from);
+        let error =
+            run::<SemVer>(&options, "1.0.0\n1.0".as_bytes(), Vec::new());
+        assert_eq!(error.unwrap_err(), "line 2: `1.0`: not a semver");
+    }
 
-    for sv in sem_ver.iter() {
-        println!(
-            "{} {} in range",
-            sv,
-            if range.contains(sv) { "is" } else { "isn't" }
+    #[test]
+    fn filter() {
+        let input = "0.3.0 0.4.0+b 0.3.7 1.0.0-rc.1 0.3.7+b";
+        assert_eq!(
+            success(&["filter", "^0.3"], input),
+            "0.3.0\n0.3.7\n0.3.7+b\n"
         );
+        assert_eq!(success(&["filter", "=0.4.0"], input), "0.4.0+b\n");
+        assert_eq!(success(&["filter", ">=2"], input), "");
+    }
+
+    #[test]
+    fn max() {
+        let input = "0.3.0 1.0.0+x 0.3.7 1.0.0 1.1.0-rc.1";
+        assert_eq!(success(&["max", "^0.3"], input), "0.3.7\n");
+        // both `1.0.0` are the same version, the last one is printed
+        assert_eq!(success(&["max", "*"], input), "1.0.0\n");
+        assert_eq!(success(&["max", "*"], "1.0.0 1.0.0+x"), "1.0.0+x\n");
+        assert_eq!(cli(&["max", ">=2"], input), (ExitCode::FAILURE, "".into()));
     }
 }
@@ -0,0 +1,271 @@
+use std::{
+    fmt,
+    ops::{Bound, RangeBounds},
+    str::FromStr,
+};
+
+use regex::Regex;
+
+use crate::{semver::SemVer, version::Version};
+
+/// Lower and upper bounds of versions, that satisfy a comparator
+pub type Bounds<V> = (Bound<V>, Bound<V>);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Op {
+    Exact,
+    Greater,
+    GreaterEq,
+    Less,
+    LessEq,
+    Tilde,
+    Caret,
+    Wildcard,
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Op::Exact => "=",
+            Op::Greater => ">",
+            Op::GreaterEq => ">=",
+            Op::Less => "<",
+            Op::LessEq => "<=",
+            Op::Tilde => "~",
+            Op::Caret => "^",
+            Op::Wildcard => "",
+        })
+    }
+}
+
+/// Single condition of a requirement, e.g. `>=1.2` or `~0.3.1-rc`
+///
+/// Missing components are allowed and are treated the same way cargo does:
+/// `^1.2` is `>=1.2.0, <2.0.0`, `=1.2` is `>=1.2.0, <1.3.0`, `1.*` is
+/// `>=1.0.0, <2.0.0`. Caret keeps the first component, that is not the
+/// lowest, and tilde keeps the first two, whatever the scheme is.
+#[derive(Clone, Debug)]
+pub struct Comparator<V = SemVer> {
+    pub op: Op,
+    /// Lowest version with all specified components, `None` for `*`
+    pub version: Option<V>,
+    /// Number of specified numeric components
+    pub precision: usize,
+}
+
+impl<V: Version> Comparator<V> {
+    /// Bounds of the matching versions, an upper bound past the greatest
+    /// version is `Unbounded`
+    pub fn bounds(&self) -> Bounds<V> {
+        use Bound::*;
+
+        let Some(floor) = self.version.clone() else {
+            return (Unbounded, Unbounded);
+        };
+        let exact = self.precision == V::LEVELS.len();
+        // lowest version, that differs in some of the specified components,
+        // `None` if there is no such version
+        let ceil = |level: usize| floor.bump(level);
+        let below = |ceil: Option<V>| ceil.map_or(Unbounded, Excluded);
+
+        match self.op {
+            Op::Exact if exact => (Included(floor.clone()), Included(floor)),
+            Op::Exact | Op::Wildcard => {
+                (Included(floor.clone()), below(ceil(self.precision - 1)))
+            }
+            Op::Greater if exact => (Excluded(floor), Unbounded),
+            Op::Greater => match ceil(self.precision - 1) {
+                Some(ceil) => (Included(ceil), Unbounded),
+                // no version is greater
+                None => (Excluded(floor.clone()), Excluded(floor)),
+            },
+            Op::GreaterEq => (Included(floor), Unbounded),
+            Op::Less => (Unbounded, Excluded(floor)),
+            Op::LessEq if exact => (Unbounded, Included(floor)),
+            Op::LessEq => (Unbounded, below(ceil(self.precision - 1))),
+            Op::Tilde => {
+                let ceil = ceil(self.precision.min(2) - 1);
+                (Included(floor), below(ceil))
+            }
+            Op::Caret => {
+                let level = (0..self.precision)
+                    .find(|level| !floor.is_lowest(*level))
+                    .unwrap_or(self.precision - 1);
+                let ceil = ceil(level);
+                (Included(floor), below(ceil))
+            }
+        }
+    }
+
+    pub fn matches(&self, version: &V) -> bool {
+        // build metadata does not take part in matching
+        let version = version.with_prerelease(version.prerelease());
+        self.bounds().contains(&version)
+    }
+
+    /// Whether prerelease `version` is explicitly opted in by this comparator
+    fn allows_prerelease(&self, version: &V) -> bool {
+        match &self.version {
+            Some(floor) => {
+                floor.is_prerelease() && floor.release() == version.release()
+            }
+            None => false,
+        }
+    }
+}
+
+impl<V: Version> fmt::Display for Comparator<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some(version) = &self.version else {
+            return f.write_str("*");
+        };
+        write!(f, "{}", self.op)?;
+        version.fmt_parts(self.precision, f)?;
+        if self.op == Op::Wildcard {
+            f.write_str(".*")?;
+        }
+        if let Some(prerelease) = version.prerelease() {
+            write!(f, "-{prerelease}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Comma-separated list of comparators, all of which must match
+///
+/// Prerelease versions only match if one of the comparators names a
+/// prerelease of the same release, so `^1.0.0` does not pick up
+/// `1.1.0-alpha` but `>=1.1.0-alpha` does.
+#[derive(Clone, Debug)]
+pub struct VersionReq<V = SemVer> {
+    pub comparators: Vec<Comparator<V>>,
+}
+
+impl<V: Version> VersionReq<V> {
+    pub fn matches(&self, version: &V) -> bool {
+        self.comparators.iter().all(|c| c.matches(version))
+            && (!version.is_prerelease()
+                || self
+                    .comparators
+                    .iter()
+                    .any(|c| c.allows_prerelease(version)))
+    }
+
+    /// Greatest of `versions`, that matches the requirement, the last one
+    /// of equal versions
+    pub fn max_matching<'v, I>(&self, versions: I) -> Option<&'v V>
+    where
+        I: IntoIterator<Item = &'v V>,
+    {
+        versions.into_iter().filter(|v| self.matches(v)).max()
+    }
+}
+
+impl<V: Version> fmt::Display for VersionReq<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut comparators = self.comparators.iter();
+        if let Some(first) = comparators.next() {
+            write!(f, "{first}")?;
+        }
+        for comparator in comparators {
+            write!(f, ", {comparator}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum ReqParseError {
+    Empty,
+    NotAComparator(Box<str>),
+}
+
+impl fmt::Display for ReqParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReqParseError::Empty => f.write_str("empty requirement"),
+            ReqParseError::NotAComparator(s) => {
+                write!(f, "`{s}` is not a version comparator")
+            }
+        }
+    }
+}
+
+fn is_wildcard(part: &str) -> bool {
+    matches!(part, "*" | "x" | "X")
+}
+
+impl<V: Version> FromStr for Comparator<V> {
+    type Err = ReqParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let regex = Regex::new(concat!(
+            r"^(?P<op>=|>=|<=|>|<|~|\^)?\s*[vV]?",
+            r"(?P<parts>(?:\d+|[*xX])(?:\.(?:\d+|[*xX]))*)",
+            r"(?:-(?P<prerelease>[0-9a-zA-Z-]+(?:\.[0-9a-zA-Z-]+)*))?",
+            r"$",
+        ))
+        .unwrap();
+        let err = || ReqParseError::NotAComparator(Box::from(s));
+        let capture = regex.captures(s).ok_or_else(err)?;
+
+        let mut parts = capture
+            .name("parts")
+            .map_or("", |m| m.as_str())
+            .split('.')
+            .collect::<Vec<_>>();
+        let precision = parts
+            .iter()
+            .position(|part| is_wildcard(part))
+            .unwrap_or(parts.len());
+        let wildcard = precision < parts.len();
+        // a number after a wildcard, like `1.*.3`
+        if !parts[precision..].iter().all(|part| is_wildcard(part))
+            || parts.len() > V::LEVELS.len()
+        {
+            return Err(err());
+        }
+        parts.truncate(precision);
+
+        let op = match capture.name("op").map(|m| m.as_str()) {
+            None if wildcard => Op::Wildcard,
+            None | Some("^") => Op::Caret,
+            Some("=") => Op::Exact,
+            Some(">") => Op::Greater,
+            Some(">=") => Op::GreaterEq,
+            Some("<") => Op::Less,
+            Some("<=") => Op::LessEq,
+            Some("~") => Op::Tilde,
+            Some(_) => unreachable!(),
+        };
+        let prerelease = capture.name("prerelease").map(|m| m.as_str());
+        if prerelease.is_some() && precision < V::LEVELS.len() {
+            return Err(err());
+        }
+
+        let version = match precision {
+            0 => None,
+            _ => Some(V::from_parts(&parts, prerelease).ok_or_else(err)?),
+        };
+        Ok(Comparator {
+            op,
+            version,
+            precision,
+        })
+    }
+}
+
+impl<V: Version> FromStr for VersionReq<V> {
+    type Err = ReqParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().is_empty() {
+            return Err(ReqParseError::Empty);
+        }
+        let comparators = s
+            .split(',')
+            .map(|c| c.trim().parse())
+            .collect::<Result<_, _>>()?;
+        Ok(VersionReq { comparators })
+    }
+}
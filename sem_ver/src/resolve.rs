@@ -0,0 +1,344 @@
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+use crate::{req::VersionReq, semver::SemVer, version::Version};
+
+/// Requirement of a single package
+#[derive(Clone, Debug)]
+pub struct Dependency<V = SemVer> {
+    pub name: Box<str>,
+    pub req: VersionReq<V>,
+}
+
+impl<V: Version> fmt::Display for Dependency<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.name, self.req)
+    }
+}
+
+impl<V: Version> FromStr for Dependency<V> {
+    type Err = String;
+
+    /// Parses `name req`, e.g. `serde >=1.0, <2`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (name, req) = s.split_once(char::is_whitespace).unwrap_or((s, "*"));
+        Ok(Dependency {
+            name: Box::from(name),
+            req: req.parse().map_err(|e| format!("`{s}`: {e}"))?,
+        })
+    }
+}
+
+/// Parses `;`-separated list of dependencies
+pub fn parse_dependencies<V: Version>(
+    s: &str,
+) -> Result<Vec<Dependency<V>>, String> {
+    s.split(';')
+        .filter(|dep| !dep.trim().is_empty())
+        .map(str::parse)
+        .collect()
+}
+
+/// Package, that introduced a requirement
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Requirer<V = SemVer> {
+    Root,
+    Package(Box<str>, V),
+}
+
+impl<V: Version> fmt::Display for Requirer<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Requirer::Root => f.write_str("root"),
+            Requirer::Package(name, version) => write!(f, "{name} {version}"),
+        }
+    }
+}
+
+/// All known packages with dependencies of each of their versions
+///
+/// Could be parsed from a manifest with a line per package version:
+///
+/// ```text
+/// # name version: dependencies
+/// app 1.0.0: log ^0.4; serde >=1.0, <2
+/// log 0.4.17
+/// ```
+#[derive(Clone, Debug)]
+pub struct Registry<V = SemVer> {
+    packages: BTreeMap<Box<str>, BTreeMap<V, Vec<Dependency<V>>>>,
+}
+
+pub type Resolution<V = SemVer> = BTreeMap<Box<str>, V>;
+
+impl<V: Version> Registry<V> {
+    pub fn new() -> Self {
+        Self {
+            packages: BTreeMap::new(),
+        }
+    }
+
+    pub fn add(
+        &mut self,
+        name: &str,
+        version: V,
+        dependencies: Vec<Dependency<V>>,
+    ) -> &mut Self {
+        self.packages
+            .entry(Box::from(name))
+            .or_default()
+            .insert(version, dependencies);
+        self
+    }
+
+    /// Finds a version for every package reachable from `root`, so that all
+    /// requirements are satisfied, preferring the greatest versions
+    pub fn resolve(
+        &self,
+        root: &[Dependency<V>],
+    ) -> Result<Resolution<V>, Conflict<V>> {
+        let mut state = State {
+            decided: BTreeMap::new(),
+            constraints: root
+                .iter()
+                .map(|dep| (Requirer::Root, dep.clone()))
+                .collect(),
+        };
+        self.solve(&mut state)?;
+        Ok(state.decided)
+    }
+
+    fn solve(&self, state: &mut State<V>) -> Result<(), Conflict<V>> {
+        let Some(name) = state
+            .constraints
+            .iter()
+            .map(|(_, dep)| &dep.name)
+            .find(|name| !state.decided.contains_key(*name))
+            .cloned()
+        else {
+            return Ok(());
+        };
+
+        let requirements = state.requirements(&name);
+        let available = self.packages.get(&name);
+        let candidates = available
+            .into_iter()
+            .flatten()
+            .rev()
+            .filter(|(version, _)| {
+                requirements.iter().all(|(_, req)| req.matches(version))
+            })
+            .collect::<Vec<_>>();
+
+        if candidates.is_empty() {
+            return Err(Conflict::Unsatisfiable {
+                name,
+                requirements,
+                available: available
+                    .into_iter()
+                    .flat_map(BTreeMap::keys)
+                    .cloned()
+                    .collect(),
+            });
+        }
+
+        let mut tried = Vec::new();
+        for (version, dependencies) in candidates {
+            let requirer = Requirer::Package(name.clone(), version.clone());
+            let len = state.constraints.len();
+            state.decided.insert(name.clone(), version.clone());
+            state.constraints.extend(
+                dependencies
+                    .iter()
+                    .map(|dep| (requirer.clone(), dep.clone())),
+            );
+
+            let conflict = match state.check(dependencies) {
+                Err(conflict) => conflict,
+                Ok(()) => match self.solve(state) {
+                    Ok(()) => return Ok(()),
+                    Err(conflict) => conflict,
+                },
+            };
+
+            state.decided.remove(&name);
+            state.constraints.truncate(len);
+
+            // choosing another version would not help,
+            // if this one was not the reason of the conflict
+            if !conflict.involves(&requirer) {
+                return Err(conflict);
+            }
+            tried.push((version.clone(), conflict));
+        }
+
+        Err(Conflict::Exhausted {
+            name,
+            requirements,
+            tried,
+        })
+    }
+}
+
+struct State<V> {
+    decided: Resolution<V>,
+    constraints: Vec<(Requirer<V>, Dependency<V>)>,
+}
+
+impl<V: Version> State<V> {
+    fn requirements(&self, name: &str) -> Vec<(Requirer<V>, VersionReq<V>)> {
+        self.constraints
+            .iter()
+            .filter(|(_, dep)| &*dep.name == name)
+            .map(|(requirer, dep)| (requirer.clone(), dep.req.clone()))
+            .collect()
+    }
+
+    /// Checks, that already decided packages satisfy new `dependencies`
+    fn check(&self, dependencies: &[Dependency<V>]) -> Result<(), Conflict<V>> {
+        for dep in dependencies {
+            let Some(version) = self.decided.get(&dep.name) else {
+                continue;
+            };
+            if !dep.req.matches(version) {
+                return Err(Conflict::Unsatisfiable {
+                    name: dep.name.clone(),
+                    requirements: self.requirements(&dep.name),
+                    available: vec![version.clone()],
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Explanation of why there is no solution
+#[derive(Clone, Debug)]
+pub enum Conflict<V = SemVer> {
+    /// None of `available` versions satisfy all `requirements`
+    Unsatisfiable {
+        name: Box<str>,
+        requirements: Vec<(Requirer<V>, VersionReq<V>)>,
+        available: Vec<V>,
+    },
+    /// Every version, that satisfies `requirements`, leads to a conflict
+    Exhausted {
+        name: Box<str>,
+        requirements: Vec<(Requirer<V>, VersionReq<V>)>,
+        tried: Vec<(V, Conflict<V>)>,
+    },
+}
+
+impl<V: Version> Conflict<V> {
+    fn involves(&self, requirer: &Requirer<V>) -> bool {
+        match self {
+            Conflict::Unsatisfiable {
+                name,
+                requirements,
+                available,
+            } => {
+                // already decided version could be the culprit as well
+                let decided = match requirer {
+                    Requirer::Package(n, v) => {
+                        n == name && available.contains(v)
+                    }
+                    Requirer::Root => false,
+                };
+                decided || requirements.iter().any(|(r, _)| r == requirer)
+            }
+            Conflict::Exhausted {
+                requirements,
+                tried,
+                ..
+            } => {
+                requirements.iter().any(|(r, _)| r == requirer)
+                    || tried.iter().any(|(_, c)| c.involves(requirer))
+            }
+        }
+    }
+
+    fn fmt_indented(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        indent: usize,
+    ) -> fmt::Result {
+        let pad = "  ".repeat(indent);
+        let (name, requirements) = match self {
+            Conflict::Unsatisfiable {
+                name, requirements, ..
+            }
+            | Conflict::Exhausted {
+                name, requirements, ..
+            } => (name, requirements),
+        };
+
+        match self {
+            Conflict::Unsatisfiable { available, .. }
+                if available.is_empty() =>
+            {
+                writeln!(f, "{pad}no versions of `{name}` are known:")?
+            }
+            Conflict::Unsatisfiable { .. } => {
+                writeln!(f, "{pad}no version of `{name}` satisfies:")?
+            }
+            Conflict::Exhausted { .. } => writeln!(
+                f,
+                "{pad}every version of `{name}` leads to a conflict:"
+            )?,
+        }
+        for (requirer, req) in requirements {
+            writeln!(f, "{pad}  {requirer} requires {name} {req}")?;
+        }
+
+        match self {
+            Conflict::Unsatisfiable { available, .. } => {
+                if !available.is_empty() {
+                    let available =
+                        available.iter().map(V::to_string).collect::<Vec<_>>();
+                    writeln!(f, "{pad}  available: {}", available.join(", "))?;
+                }
+            }
+            Conflict::Exhausted { tried, .. } => {
+                for (version, conflict) in tried {
+                    writeln!(f, "{pad}  {name} {version}:")?;
+                    conflict.fmt_indented(f, indent + 2)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<V: Version> fmt::Display for Conflict<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_indented(f, 0)
+    }
+}
+
+impl<V: Version> FromStr for Registry<V> {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut registry = Registry::new();
+        for (line_num, line) in s.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let err = |e: String| format!("line {}: {e}", line_num + 1);
+
+            let (package, dependencies) =
+                line.split_once(':').unwrap_or((line, ""));
+            let Some((name, version)) = package.trim().split_once(' ') else {
+                return Err(err(format!("`{package}` has no version")));
+            };
+            let version = version
+                .trim()
+                .parse()
+                .map_err(|e| err(format!("`{version}`: {e}")))?;
+            let dependencies = parse_dependencies(dependencies).map_err(err)?;
+            registry.add(name, version, dependencies);
+        }
+        Ok(registry)
+    }
+}
@@ -0,0 +1,263 @@
+use std::{cmp::*, fmt, str::FromStr};
+
+use num_integer::Integer;
+use num_traits::CheckedAdd;
+use regex::Regex;
+
+use crate::version::{cmp_prerelease, Version};
+
+#[derive(Clone, Debug)]
+pub struct SemVer<
+    Major: Integer = u16,
+    Minor: Integer = u16,
+    Patch: Integer = u32,
+> {
+    pub major: Major,
+    pub minor: Minor,
+    pub patch: Patch,
+    pub prerelease: Option<Box<str>>,
+    pub buildmetadata: Option<Box<str>>,
+}
+
+impl<Major, Minor, Patch> SemVer<Major, Minor, Patch>
+where
+    Major: Integer,
+    Minor: Integer,
+    Patch: Integer,
+{
+    pub fn new(major: Major, minor: Minor, patch: Patch) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+            prerelease: None,
+            buildmetadata: None,
+        }
+    }
+}
+
+impl<Major, Minor, Patch> Version for SemVer<Major, Minor, Patch>
+where
+    Major: Integer + CheckedAdd + Clone + fmt::Display,
+    Minor: Integer + CheckedAdd + Clone + fmt::Display,
+    Patch: Integer + CheckedAdd + Clone + fmt::Display,
+{
+    const LEVELS: &'static [&'static str] = &["major", "minor", "patch"];
+
+    fn from_parts(parts: &[&str], prerelease: Option<&str>) -> Option<Self> {
+        fn part<N: Integer>(part: Option<&&str>) -> Option<N> {
+            match part {
+                Some(part) => N::from_str_radix(part, 10).ok(),
+                None => Some(N::zero()),
+            }
+        }
+
+        Some(SemVer {
+            prerelease: prerelease.map(Box::from),
+            ..Self::new(
+                part(parts.first())?,
+                part(parts.get(1))?,
+                part(parts.get(2))?,
+            )
+        })
+    }
+
+    fn fmt_parts(
+        &self,
+        precision: usize,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        match precision {
+            0 => Ok(()),
+            1 => write!(f, "{}", self.major),
+            2 => write!(f, "{}.{}", self.major, self.minor),
+            _ => write!(f, "{}.{}.{}", self.major, self.minor, self.patch),
+        }
+    }
+
+    fn is_lowest(&self, level: usize) -> bool {
+        match level {
+            0 => self.major.is_zero(),
+            1 => self.minor.is_zero(),
+            _ => self.patch.is_zero(),
+        }
+    }
+
+    fn bump(&self, level: usize) -> Option<Self> {
+        Some(match level {
+            0 => Self::new(
+                self.major.checked_add(&Major::one())?,
+                Minor::zero(),
+                Patch::zero(),
+            ),
+            1 => Self::new(
+                self.major.clone(),
+                self.minor.checked_add(&Minor::one())?,
+                Patch::zero(),
+            ),
+            _ => Self::new(
+                self.major.clone(),
+                self.minor.clone(),
+                self.patch.checked_add(&Patch::one())?,
+            ),
+        })
+    }
+
+    fn prerelease(&self) -> Option<&str> {
+        self.prerelease.as_deref()
+    }
+
+    fn with_prerelease(&self, prerelease: Option<&str>) -> Self {
+        Self {
+            prerelease: prerelease.map(Box::from),
+            ..Self::new(
+                self.major.clone(),
+                self.minor.clone(),
+                self.patch.clone(),
+            )
+        }
+    }
+}
+
+impl<Major, Minor, Patch> fmt::Display for SemVer<Major, Minor, Patch>
+where
+    Major: Integer + fmt::Display,
+    Minor: Integer + fmt::Display,
+    Patch: Integer + fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+        if let Some(prerelease) = &self.prerelease {
+            write!(f, "-{}", prerelease)?;
+        }
+        if let Some(buildmetadata) = &self.buildmetadata {
+            write!(f, "+{}", buildmetadata)?;
+        }
+        Ok(())
+    }
+}
+
+impl<Major, Minor, Patch> Eq for SemVer<Major, Minor, Patch>
+where
+    Major: Integer,
+    Minor: Integer,
+    Patch: Integer,
+{
+}
+
+impl<Major, Minor, Patch> PartialEq for SemVer<Major, Minor, Patch>
+where
+    Major: Integer,
+    Minor: Integer,
+    Patch: Integer,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl<Major, Minor, Patch> Ord for SemVer<Major, Minor, Patch>
+where
+    Major: Integer,
+    Minor: Integer,
+    Patch: Integer,
+{
+    /// Precedence of the versions, build metadata is ignored, so
+    /// `1.0.0` and `1.0.0+build` are equal
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.major
+            .cmp(&other.major)
+            .then_with(|| self.minor.cmp(&other.minor))
+            .then_with(|| self.patch.cmp(&other.patch))
+            .then_with(|| {
+                cmp_prerelease(
+                    self.prerelease.as_deref(),
+                    other.prerelease.as_deref(),
+                )
+            })
+    }
+}
+
+impl<Major, Minor, Patch> PartialOrd for SemVer<Major, Minor, Patch>
+where
+    Major: Integer,
+    Minor: Integer,
+    Patch: Integer,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum SemVerParseError {
+    NotASemVer,
+    NotSingleSemVer,
+}
+
+impl fmt::Display for SemVerParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SemVerParseError::NotASemVer => f.write_str("not a semver"),
+            SemVerParseError::NotSingleSemVer => {
+                f.write_str("more than one semver")
+            }
+        }
+    }
+}
+
+impl<Major, Minor, Patch> FromStr for SemVer<Major, Minor, Patch>
+where
+    Major: Integer,
+    Minor: Integer,
+    Patch: Integer,
+{
+    type Err = SemVerParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let regex = Regex::new(concat!(
+            r"^[vV]?",
+            r"(?P<major>0|[1-9]\d*)",
+            r"\.(?P<minor>0|[1-9]\d*)",
+            r"\.(?P<patch>0|[1-9]\d*)",
+            r"(?:-(?P<prerelease>(?:0|[1-9]\d*|\d*[a-zA-Z-][0-9a-zA-Z-]*)(?:\.(?:0|[1-9]\d*|\d*[a-zA-Z-][0-9a-zA-Z-]*))*))?",
+            r"(?:\+(?P<buildmetadata>[0-9a-zA-Z-]+(?:\.[0-9a-zA-Z-]+)*))?",
+            r"$",
+        )).unwrap();
+        let mut captures = regex.captures_iter(s);
+
+        match (captures.next(), captures.next()) {
+            (Some(capture), None) => {
+                // SAFETY: obligatory regex groups
+                let (major, minor, patch) = unsafe {
+                    (
+                        capture.name("major").unwrap_unchecked().as_str(),
+                        capture.name("minor").unwrap_unchecked().as_str(),
+                        capture.name("patch").unwrap_unchecked().as_str(),
+                    )
+                };
+                // regex contains only decimal digits, but they may not fit
+                // into the chosen integer width
+                fn overflow<E>(_: E) -> SemVerParseError {
+                    SemVerParseError::NotASemVer
+                }
+                Ok(SemVer {
+                    major: Major::from_str_radix(major, 10)
+                        .map_err(overflow)?,
+                    minor: Minor::from_str_radix(minor, 10)
+                        .map_err(overflow)?,
+                    patch: Patch::from_str_radix(patch, 10)
+                        .map_err(overflow)?,
+                    prerelease: capture
+                        .name("prerelease")
+                        .map(|m| Box::from(m.as_str())),
+                    buildmetadata: capture
+                        .name("buildmetadata")
+                        .map(|m| Box::from(m.as_str())),
+                })
+            }
+            (Some(_), Some(_)) => Err(SemVerParseError::NotSingleSemVer),
+            _ => Err(SemVerParseError::NotASemVer),
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use std::{cmp::Ordering, fmt, str::FromStr};
+
+/// Versioning scheme with a fixed number of numeric components, optionally
+/// followed by a `-prerelease` tag
+///
+/// Requirements and resolution only rely on this trait, so they work the
+/// same way for [`SemVer`](crate::semver::SemVer),
+/// [`CalVer`](crate::calver::CalVer) and others.
+pub trait Version:
+    Clone + Ord + fmt::Display + FromStr<Err: fmt::Display>
+{
+    /// Names of numeric components, starting from the most significant one
+    const LEVELS: &'static [&'static str];
+
+    /// Builds a version from the leading numeric components, the rest of
+    /// them get their lowest possible values
+    ///
+    /// Returns `None` if some component is out of range.
+    fn from_parts(parts: &[&str], prerelease: Option<&str>) -> Option<Self>;
+
+    /// Writes the first `precision` numeric components
+    fn fmt_parts(
+        &self,
+        precision: usize,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result;
+
+    /// Whether component at `level` has its lowest possible value
+    fn is_lowest(&self, level: usize) -> bool;
+
+    /// Increments component at `level`, resetting all of the lower ones and
+    /// dropping prerelease
+    ///
+    /// Returns `None` if the component is already the greatest one.
+    fn bump(&self, level: usize) -> Option<Self>;
+
+    fn prerelease(&self) -> Option<&str>;
+
+    /// Same version with another prerelease tag and without build metadata
+    fn with_prerelease(&self, prerelease: Option<&str>) -> Self;
+
+    #[inline]
+    fn is_prerelease(&self) -> bool {
+        self.prerelease().is_some()
+    }
+
+    /// Same version without prerelease and build metadata
+    #[inline]
+    fn release(&self) -> Self {
+        self.with_prerelease(None)
+    }
+
+    /// Moves to the next prerelease
+    ///
+    /// Release version moves to the prerelease of the next least significant
+    /// component (`1.2.3` -> `1.2.4-0`), otherwise the last numeric
+    /// identifier of the prerelease is incremented (`1.0.0-rc.1` ->
+    /// `1.0.0-rc.2`, `1.0.0-rc` -> `1.0.0-rc.0`).
+    ///
+    /// Returns `None` if the incremented number overflows.
+    fn bump_pre(&self) -> Option<Self> {
+        match self.prerelease() {
+            Some(pre) => {
+                Some(self.with_prerelease(Some(&bump_prerelease(pre)?)))
+            }
+            None => {
+                let next = self.bump(Self::LEVELS.len() - 1)?;
+                Some(next.with_prerelease(Some("0")))
+            }
+        }
+    }
+
+    /// Increments component named `level`, or prerelease for `pre`
+    fn bump_named(&self, level: &str) -> Result<Self, String> {
+        let bumped = if level == "pre" {
+            self.bump_pre()
+        } else {
+            match Self::LEVELS.iter().position(|name| *name == level) {
+                Some(level) => self.bump(level),
+                None => {
+                    return Err(format!(
+                        "unknown level `{level}`, expected {} or pre",
+                        Self::LEVELS.join(", ")
+                    ))
+                }
+            }
+        };
+        bumped.ok_or_else(|| format!("`{self}` overflows when bumping {level}"))
+    }
+}
+
+/// Increments the last numeric identifier, `None` if it overflows
+fn bump_prerelease(pre: &str) -> Option<String> {
+    let (init, last) = match pre.rsplit_once('.') {
+        Some((init, last)) => (Some(init), last),
+        None => (None, pre),
+    };
+    Some(match (init, last.parse::<u64>()) {
+        (Some(init), Ok(n)) => format!("{init}.{}", n.checked_add(1)?),
+        (None, Ok(n)) => n.checked_add(1)?.to_string(),
+        (_, Err(_)) => format!("{pre}.0"),
+    })
+}
+
+/// Release is greater than any of its prereleases, prerelease identifiers
+/// are compared one by one
+///
+/// Numeric identifiers are compared as numbers and have lower precedence
+/// than alphanumeric ones, so `rc.2 < rc.10 < rc.a`.
+pub fn cmp_prerelease(first: Option<&str>, second: Option<&str>) -> Ordering {
+    match (first, second) {
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) => Ordering::Greater,
+        (Some(_), None) => Ordering::Less,
+        (Some(first), Some(second)) => {
+            let key = |id| match str::parse::<u64>(id) {
+                Ok(num) => (false, num, id),
+                Err(_) => (true, 0, id),
+            };
+            first.split('.').map(key).cmp(second.split('.').map(key))
+        }
+    }
+}
+
+/// Splits `1.2.3-rc.1` into `["1", "2", "3"]` and `Some("rc.1")`
+///
+/// Every component must be a decimal number without leading zeros, unless
+/// `leading_zeros` is set.
+fn split_parts(
+    s: &str,
+    leading_zeros: bool,
+) -> Option<(Vec<&str>, Option<&str>)> {
+    let s = s.strip_prefix(['v', 'V']).unwrap_or(s);
+    let (parts, prerelease) = match s.split_once('-') {
+        Some((parts, prerelease)) => (parts, Some(prerelease)),
+        None => (s, None),
+    };
+    let parts = parts.split('.').collect::<Vec<_>>();
+
+    let is_number = |part: &str| {
+        !part.is_empty()
+            && part.bytes().all(|b| b.is_ascii_digit())
+            && (leading_zeros || part == "0" || !part.starts_with('0'))
+    };
+    let is_identifier = |id: &str| {
+        !id.is_empty()
+            && id.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-')
+    };
+    if !parts.iter().all(|part| is_number(part))
+        || !prerelease.is_none_or(|pre| pre.split('.').all(is_identifier))
+    {
+        return None;
+    }
+    Some((parts, prerelease))
+}
+
+/// Parses version with all of the `V::LEVELS` components present
+pub fn parse_full<V: Version>(s: &str, leading_zeros: bool) -> Option<V> {
+    let (parts, prerelease) = split_parts(s, leading_zeros)?;
+    if parts.len() != V::LEVELS.len() {
+        return None;
+    }
+    V::from_parts(&parts, prerelease)
+}